@@ -0,0 +1,166 @@
+//! Derive macro for [passarg](https://docs.rs/passarg);
+//! use it through the `derive` feature of passarg,
+//! as `passarg::PassArgs`.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Fields, LitInt, LitStr, Type};
+
+/// Derives `passarg::PassArgs` for a struct of passphrase arguments.
+///
+/// Each field annotated with `#[passarg(order = N)]` is a passphrase argument,
+/// of type `Source`, `String` or `OsString`, or an `Option` of one.
+/// The derived `read_all()` reads the arguments in ascending `order`,
+/// into the same-named fields of the generated `{Name}Secrets` struct:
+/// a `SecretString`, or an `Option<SecretString>` for an `Option` field
+/// that is absent.
+/// `#[passarg(order = N, default = "spec")]` on an `Option` field
+/// reads `spec` instead when the field is `None`.
+/// Other fields are left alone.
+#[proc_macro_derive(PassArgs, attributes(passarg))]
+pub fn derive_pass_args(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct PassArg {
+    field: syn::Field,
+    order: u64,
+    default: Option<LitStr>,
+    optional: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(
+            Span::call_site(),
+            "PassArgs can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new(
+            data.fields.span(),
+            "PassArgs requires named fields",
+        ));
+    };
+    let mut args = Vec::new();
+    for field in &fields.named {
+        if let Some(arg) = parse_field(field)? {
+            args.push(arg);
+        }
+    }
+    args.sort_by_key(|arg| arg.order);
+    for pair in args.windows(2) {
+        if pair[0].order == pair[1].order {
+            return Err(Error::new(
+                pair[1].field.span(),
+                format!("another field is already read in order {}", pair[1].order),
+            ));
+        }
+    }
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let secrets = format_ident!("{}Secrets", name);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut decls = Vec::new();
+    let mut reads = Vec::new();
+    let mut names = Vec::new();
+    for arg in &args {
+        let ident = arg.field.ident.as_ref().unwrap();
+        let field_vis = &arg.field.vis;
+        let (ty, read) = match (arg.optional, &arg.default) {
+            (false, _) => (
+                quote!(::passarg::SecretString),
+                quote!(::passarg::__private::read_required(reader, &self.#ident)),
+            ),
+            (true, None) => (
+                quote!(::core::option::Option<::passarg::SecretString>),
+                quote!(::passarg::__private::read_optional(reader, &self.#ident)),
+            ),
+            (true, Some(default)) => (
+                quote!(::passarg::SecretString),
+                quote!(::passarg::__private::read_defaulted(reader, &self.#ident, #default)),
+            ),
+        };
+        decls.push(quote!(#field_vis #ident: #ty));
+        reads.push(quote!(let #ident = #read?;));
+        names.push(ident);
+    }
+    let doc = format!("Passwords read from [`{name}`] by `PassArgs::read_all()`.");
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug)]
+        #vis struct #secrets {
+            #(#decls,)*
+        }
+
+        impl #impl_generics ::passarg::PassArgs for #name #ty_generics #where_clause {
+            type Secrets = #secrets;
+
+            fn read_all(
+                &self,
+                reader: &mut ::passarg::Reader<'_>,
+            ) -> ::core::result::Result<#secrets, ::passarg::Error> {
+                #(#reads)*
+                ::core::result::Result::Ok(#secrets { #(#names),* })
+            }
+        }
+    })
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<Option<PassArg>> {
+    let mut order = None;
+    let mut default = None;
+    let mut annotated = false;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("passarg")) {
+        annotated = true;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("order") {
+                order = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<u64>()?);
+            } else if meta.path.is_ident("default") {
+                default = Some(meta.value()?.parse::<LitStr>()?);
+            } else {
+                return Err(meta.error("expected `order` or `default`"));
+            }
+            Ok(())
+        })?;
+    }
+    if !annotated {
+        return Ok(None);
+    }
+    let Some(order) = order else {
+        return Err(Error::new(
+            field.span(),
+            "missing `order = N` in #[passarg(...)]",
+        ));
+    };
+    let optional = is_option(&field.ty);
+    if let (false, Some(default)) = (optional, &default) {
+        return Err(Error::new(
+            default.span(),
+            "`default` requires an `Option` field",
+        ));
+    }
+    Ok(Some(PassArg {
+        field: field.clone(),
+        order,
+        default,
+        optional,
+    }))
+}
+
+fn is_option(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.qself.is_none()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|s| s.ident == "Option")
+}
@@ -0,0 +1,49 @@
+use clap::Parser;
+use passarg::{PassArgs, Reader, Source};
+
+#[derive(Parser, PassArgs)]
+struct Cli {
+    #[arg(long)]
+    #[passarg(order = 2)]
+    pass_out: Source,
+
+    #[arg(long)]
+    #[passarg(order = 1, default = "pass:default-in")]
+    pass_in: Option<String>,
+
+    #[arg(long)]
+    #[passarg(order = 3)]
+    pass_extra: Option<String>,
+
+    #[arg(long)]
+    verbose: bool,
+}
+
+fn temp_file(name: &str, contents: &[u8]) -> std::path::PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("passarg-derive-test-{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_read_all_in_order() {
+    let path = temp_file("order", b"first\nsecond\n");
+    let spec = format!("file:{}", path.display());
+    let cli = Cli::parse_from(["test", "--pass-out", &spec, "--pass-in", &spec]);
+    let secrets = cli.read_all(&mut Reader::new()).unwrap();
+    assert_eq!(secrets.pass_in.expose(), "first");
+    assert_eq!(secrets.pass_out.expose(), "second");
+    assert!(secrets.pass_extra.is_none());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_default() {
+    let cli = Cli::parse_from(["test", "--pass-out", "pass:out", "--pass-extra", "pass:x"]);
+    let secrets: CliSecrets = cli.read_all(&mut Reader::new()).unwrap();
+    assert_eq!(secrets.pass_in.expose(), "default-in");
+    assert_eq!(secrets.pass_out.expose(), "out");
+    assert_eq!(secrets.pass_extra.unwrap().expose(), "x");
+    assert!(!cli.verbose);
+}
@@ -0,0 +1,529 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::ffi::OsStringExt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use tokio::io::unix::AsyncFd;
+use tokio::task::spawn_blocking;
+use zeroize::Zeroizing;
+
+use crate::error::ResultExt;
+use crate::files::{FileStream, Files, Found, Target};
+use crate::line::{open_without_waiting, wait_readable, FdFile, FileId};
+use crate::{Error, Reader, SecretBytes, SecretString, Settings, Source};
+
+/// Password argument reader for [tokio](https://tokio.rs) applications.
+///
+/// `AsyncReader` reads the same sources as [`Reader`], with the same settings,
+/// and file-like sources share streams of lines the same way.
+/// Files and file descriptors that `AsyncReader` opens or closes itself are read with async I/O,
+/// or on the blocking thread pool where they cannot be polled, as with regular files.
+/// Standard input and file descriptors left open (see [`Reader::with_close_fds()`])
+/// are read on the blocking thread pool, like tokio's own `stdin()` does.
+/// Other sources that may block, such as prompts, commands and
+/// [custom schemes](crate::SourceProvider), are read on the blocking thread pool
+/// (see [`tokio::task::spawn_blocking()`]),
+/// each by a fresh [`Reader`] that does not share streams with the `AsyncReader`.
+///
+/// Set up an `AsyncReader` with the builder methods of [`Reader`], then convert it:
+///
+/// ```rust
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), passarg::Error> {
+/// use passarg::{AsyncReader, Reader};
+///
+/// let mut r = AsyncReader::from(Reader::new().with_eof_as_empty(true));
+/// assert_eq!(r.read_pass_arg("pass:hunter2").await?, "hunter2");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// While a file descriptor that it polls, such as a pipe, is open in `AsyncReader`,
+/// it is in non-blocking mode;
+/// the mode is restored when `AsyncReader` goes out of scope.
+/// The timeout set by [`Reader::with_timeout()`] applies as well.
+/// The methods of `AsyncReader` must be called within a tokio runtime with I/O enabled.
+/// A read that times out or is cancelled keeps what it read of the line for the next read,
+/// except that a read on the blocking thread pool that is cancelled
+/// may lose the input it was reading.
+#[derive(Default)]
+pub struct AsyncReader {
+    files: Files<AsyncStream>,
+    settings: Settings,
+}
+
+impl From<Reader<'_>> for AsyncReader {
+    /// Creates an `AsyncReader` with the settings of `reader`.
+    ///
+    /// Files opened by `reader` are not carried over, and are closed.
+    fn from(reader: Reader<'_>) -> Self {
+        Self {
+            files: Files::default(),
+            settings: reader.settings.clone(),
+        }
+    }
+}
+
+type BoxedRead<'a> =
+    Pin<Box<dyn Future<Output = Result<(SecretBytes, Source), Error>> + Send + 'a>>;
+
+impl AsyncReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Same as [`Reader::parse_pass_arg()`].
+    pub fn parse_pass_arg(&self, arg: &str) -> Result<Source, Error> {
+        self.settings.parse_pass_arg(arg)
+    }
+
+    /// Same as [`Reader::read_pass_arg()`].
+    pub async fn read_pass_arg(&mut self, arg: &str) -> Result<String, Error> {
+        self.read_pass_arg_secret(arg)
+            .await
+            .map(SecretString::into_unprotected)
+    }
+
+    /// Same as [`Reader::read_pass_arg_secret()`].
+    pub async fn read_pass_arg_secret(&mut self, arg: &str) -> Result<SecretString, Error> {
+        let source = self.parse_pass_arg(arg)?;
+        self.read_source_secret(source).await
+    }
+
+    /// Same as [`Reader::read_pass_arg_bytes()`].
+    pub async fn read_pass_arg_bytes(&mut self, arg: &str) -> Result<SecretBytes, Error> {
+        let source = self.parse_pass_arg(arg)?;
+        self.read_source_bytes(source).await
+    }
+
+    /// Same as [`Reader::read_source()`].
+    pub async fn read_source(&mut self, source: Source) -> Result<String, Error> {
+        self.read_source_secret(source)
+            .await
+            .map(SecretString::into_unprotected)
+    }
+
+    /// Same as [`Reader::read_source_secret()`].
+    pub async fn read_source_secret(&mut self, source: Source) -> Result<SecretString, Error> {
+        self.read_source_with_origin(source)
+            .await
+            .map(|(secret, _)| secret)
+    }
+
+    /// Same as [`Reader::read_source_with_origin()`].
+    pub async fn read_source_with_origin(
+        &mut self,
+        source: Source,
+    ) -> Result<(SecretString, Source), Error> {
+        let (bytes, origin) = self.read_source_bytes_with_origin(source).await?;
+        match bytes.into_secret_string() {
+            Some(secret) => Ok((secret, origin)),
+            None => Err(Error::InvalidUtf8 { spec: origin }),
+        }
+    }
+
+    /// Same as [`Reader::read_source_bytes()`].
+    pub async fn read_source_bytes(&mut self, source: Source) -> Result<SecretBytes, Error> {
+        self.read_source_bytes_with_origin(source)
+            .await
+            .map(|(secret, _)| secret)
+    }
+
+    /// Same as [`Reader::read_source_os_string()`].
+    pub async fn read_source_os_string(&mut self, source: Source) -> Result<OsString, Error> {
+        self.read_source_bytes(source)
+            .await
+            .map(|secret| OsString::from_vec(secret.into_unprotected()))
+    }
+
+    /// Same as [`Reader::read_source_bytes_with_origin()`].
+    pub async fn read_source_bytes_with_origin(
+        &mut self,
+        source: Source,
+    ) -> Result<(SecretBytes, Source), Error> {
+        self.settings.policy.check(&source)?;
+        let Source::Chain(links) = &source else {
+            return Ok((self.read_link(&source).await?, source));
+        };
+        let mut errors = Vec::new();
+        for link in links {
+            match self.read_boxed(link.clone()).await {
+                Err(e) if e.is_not_found() => errors.push(e),
+                result => return result,
+            }
+        }
+        Err(Error::Chain {
+            spec: source,
+            errors,
+        })
+    }
+
+    /// Same as [`Reader::finish()`].
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.files.finish()
+    }
+
+    /// Boxes the recursive read of a chain link.
+    fn read_boxed(&mut self, source: Source) -> BoxedRead<'_> {
+        Box::pin(self.read_source_bytes_with_origin(source))
+    }
+
+    async fn read_link(&mut self, source: &Source) -> Result<SecretBytes, Error> {
+        let options = self.settings.line_options;
+        let registered = source
+            .scheme()
+            .is_some_and(|scheme| self.settings.providers.contains_key(scheme));
+        match source {
+            Source::Pass(_) | Source::Env(_) if !registered => {
+                Reader::with_settings(self.settings.clone()).read_builtin(source)
+            }
+            Source::File(_) | Source::FileOnce(_) | Source::Fd(_) | Source::Stdin
+                if !registered =>
+            {
+                let id = self.open_stream(source).await?;
+                let r = self.files.stream(id);
+                let mut line = Zeroizing::new(Vec::new());
+                let deadline = self
+                    .settings
+                    .timeout
+                    .map(|timeout| Instant::now() + timeout);
+                r.read_line_async(&mut line, &options, deadline)
+                    .await
+                    .with_spec(source)
+                    .map_err(|e| self.settings.timed_out(e))?;
+                let password = Reader::line_to_secret(line, source, options)?;
+                self.files.remove_once(source, id);
+                Ok(password)
+            }
+            _ => {
+                let settings = self.settings.clone();
+                let owned = source.clone();
+                blocking(source, move || {
+                    Reader::with_settings(settings).read_link(&owned)
+                })
+                .await?
+            }
+        }
+    }
+
+    /// Same as `Reader::open_stream()`, opening FIFOs on the blocking thread pool.
+    async fn open_stream(&mut self, source: &Source) -> Result<FileId, Error> {
+        let path = match Target::of(source, self.settings.close_fds) {
+            Some(Target::Path(path)) => path,
+            Some(Target::Fd(fd, owned)) => return self.files.open_fd(fd, owned, source),
+            None => unreachable!("{source} is not file-like"),
+        };
+        let canonical = match self.files.find_file(path, &self.settings, source)? {
+            Found::Open(id) => return Ok(id),
+            Found::Closed(canonical) => canonical,
+        };
+        let f = match self.settings.timeout {
+            Some(_) => open_without_waiting(&canonical),
+            None => {
+                // Opening a FIFO blocks until it has a writer.
+                let to_open = canonical.clone();
+                blocking(source, move || File::open(to_open)).await?
+            }
+        }
+        .with_spec(source)?;
+        self.files
+            .insert_file(path, canonical, f, &self.settings, source)
+    }
+}
+
+/// Runs `f` on the blocking thread pool, on behalf of `source`.
+async fn blocking<T: Send + 'static>(
+    source: &Source,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, Error> {
+    match spawn_blocking(f).await {
+        Ok(result) => Ok(result),
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(Error::Io {
+            spec: source.clone(),
+            error: io::Error::other(e),
+        }),
+    }
+}
+
+/// Open file read by a file-like source of [`AsyncReader`].
+pub(crate) enum AsyncFile {
+    File(File),
+    Fd(FdFile),
+}
+
+impl AsyncFile {
+    /// Same as [`FileStream::is_private()`].
+    fn is_private(&self) -> bool {
+        match self {
+            AsyncFile::File(_) => true,
+            AsyncFile::Fd(f) => f.is_owned(),
+        }
+    }
+
+    fn as_file(&self) -> &File {
+        match self {
+            AsyncFile::File(f) => f,
+            AsyncFile::Fd(f) => f.as_file(),
+        }
+    }
+}
+
+impl AsRawFd for AsyncFile {
+    fn as_raw_fd(&self) -> RawFd {
+        self.as_file().as_raw_fd()
+    }
+}
+
+/// [`AsyncFile`], polled by the tokio reactor if possible,
+/// or else read on the blocking thread pool.
+pub(crate) struct AsyncStream {
+    polled: Option<AsyncFd<Arc<AsyncFile>>>,
+    file: Arc<AsyncFile>,
+    /// File status flags to restore on drop, if changed.
+    flags: Option<libc::c_int>,
+}
+
+impl AsyncStream {
+    /// Wraps `file`, polling it only if private:
+    /// polling needs non-blocking mode, which would also affect the program's own reads
+    /// of a file descriptor it keeps, such as standard input.
+    fn new(file: AsyncFile) -> io::Result<Self> {
+        let file = Arc::new(file);
+        let fd = file.as_raw_fd();
+        let mut stream = Self {
+            polled: None,
+            file: Arc::clone(&file),
+            flags: None,
+        };
+        // Regular files are always ready, and cannot be polled.
+        if !file.is_private() || file.as_file().metadata()?.is_file() {
+            return Ok(stream);
+        }
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        if flags & libc::O_NONBLOCK == 0 {
+            if unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            stream.flags = Some(flags);
+        }
+        // Some files, such as /dev/null, cannot be polled either.
+        stream.polled = AsyncFd::new(file).ok();
+        if stream.polled.is_none() {
+            stream.restore_flags();
+        }
+        Ok(stream)
+    }
+
+    /// Reads into `buf`, returning the number of bytes read, or 0 at end of file.
+    ///
+    /// Fails with [`io::ErrorKind::TimedOut`] if nothing can be read by `deadline`,
+    /// having consumed nothing.
+    pub(crate) async fn read(
+        &mut self,
+        buf: &mut [u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
+        loop {
+            let result = match &self.polled {
+                Some(polled) => {
+                    let mut guard = match deadline {
+                        Some(deadline) => {
+                            tokio::time::timeout_at(deadline.into(), polled.readable())
+                                .await
+                                .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??
+                        }
+                        None => polled.readable().await?,
+                    };
+                    match guard.try_io(|polled| Read::read(&mut polled.get_ref().as_file(), buf)) {
+                        Ok(result) => result,
+                        Err(_would_block) => continue,
+                    }
+                }
+                None => {
+                    let file = Arc::clone(&self.file);
+                    let len = buf.len();
+                    // Waiting here, rather than cancelling the task, leaves no read behind
+                    // to consume input meant for the next one.
+                    let chunk = spawn_blocking(move || {
+                        if let Some(deadline) = deadline {
+                            wait_readable(file.as_raw_fd(), deadline)?;
+                        }
+                        let mut chunk = Zeroizing::new(vec![0; len]);
+                        let n = Read::read(&mut file.as_file(), &mut chunk)?;
+                        chunk.truncate(n);
+                        Ok::<_, io::Error>(chunk)
+                    })
+                    .await
+                    .map_err(io::Error::other)?;
+                    chunk.map(|chunk| {
+                        buf[..chunk.len()].copy_from_slice(&chunk);
+                        chunk.len()
+                    })
+                }
+            };
+            match result {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => return result,
+            }
+        }
+    }
+
+    fn restore_flags(&mut self) {
+        if let Some(flags) = self.flags.take() {
+            unsafe { libc::fcntl(self.file.as_raw_fd(), libc::F_SETFL, flags) };
+        }
+    }
+}
+
+impl FileStream for AsyncStream {
+    fn from_file(file: File) -> io::Result<Self> {
+        Self::new(AsyncFile::File(file))
+    }
+
+    fn from_fd(fd: RawFd, owned: bool) -> io::Result<Self> {
+        Self::new(AsyncFile::Fd(FdFile::new(fd, owned)?))
+    }
+
+    fn is_private(&self) -> bool {
+        self.file.is_private()
+    }
+}
+
+impl Drop for AsyncStream {
+    fn drop(&mut self) {
+        self.polled = None;
+        self.restore_flags();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::pipe_with;
+    use crate::SourceProvider;
+    use assert_ok::assert_ok;
+    use std::env;
+
+    #[test]
+    fn test_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        let mut r = AsyncReader::new();
+        assert_send(&r);
+        assert_send(&r.read_pass_arg("pass:omg"));
+    }
+
+    #[tokio::test]
+    async fn test_async_reader() {
+        let (read_fd, write_fd) = pipe_with(b"");
+        let mut r = AsyncReader::from(Reader::new().with_close_fds(false));
+        let fd = format!("fd:{read_fd}");
+        let writer = tokio::spawn(async move {
+            tokio::task::yield_now().await;
+            let written = unsafe { libc::write(write_fd, b"first\nsecond\n".as_ptr().cast(), 13) };
+            assert_eq!(written, 13);
+            unsafe { libc::close(write_fd) };
+        });
+        assert_eq!(assert_ok!(r.read_pass_arg(&fd).await), "first");
+        let alias = format!("file:/dev/fd/{read_fd}");
+        assert_eq!(assert_ok!(r.read_pass_arg(&alias).await), "second");
+        assert!(matches!(
+            r.read_pass_arg(&fd).await,
+            Err(Error::UnexpectedEof { .. })
+        ));
+        writer.await.unwrap();
+        // A file descriptor left open is not made non-blocking.
+        let flags = unsafe { libc::fcntl(read_fd, libc::F_GETFL) };
+        assert_eq!(flags & libc::O_NONBLOCK, 0);
+        drop(r);
+        unsafe { libc::close(read_fd) };
+
+        // One that the reader closes is polled meanwhile.
+        let (read_fd, write_fd) = pipe_with(b"first\n");
+        unsafe { libc::close(write_fd) };
+        let mut r = AsyncReader::new();
+        let fd = format!("fd:{read_fd}");
+        assert_eq!(assert_ok!(r.read_pass_arg(&fd).await), "first");
+        let flags = unsafe { libc::fcntl(read_fd, libc::F_GETFL) };
+        assert_ne!(flags & libc::O_NONBLOCK, 0);
+        drop(r);
+        assert_eq!(unsafe { libc::fcntl(read_fd, libc::F_GETFD) }, -1);
+
+        let path = env::temp_dir().join(format!("passarg-test-{}-async", std::process::id()));
+        std::fs::write(&path, b"first\nsecond\n").unwrap();
+        let mut r = AsyncReader::new();
+        let spec = format!("env:PASSARG_TEST_UNSET||file:{}", path.display());
+        let (password, origin) =
+            assert_ok!(r.read_source_with_origin(assert_ok!(spec.parse())).await);
+        assert_eq!(password.expose(), "first");
+        assert_eq!(origin, Source::File(path.clone()));
+        assert_eq!(
+            assert_ok!(r.read_source(Source::File(path.clone())).await),
+            "second"
+        );
+        assert_eq!(
+            assert_ok!(r.read_pass_arg("exec:echo hunter2").await),
+            "hunter2"
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        std::fs::write(&path, b"first\nsecond\n").unwrap();
+        let mut r = AsyncReader::new();
+        let once = Source::FileOnce(path.clone());
+        assert_eq!(assert_ok!(r.read_source(once.clone()).await), "first");
+        assert!(!path.exists());
+        assert_eq!(assert_ok!(r.read_source(once).await), "second");
+        assert_ok!(r.finish());
+
+        // Providers registered for built-in schemes replace the ones read inline.
+        struct Fixed;
+
+        impl SourceProvider for Fixed {
+            fn read(&self, _: &Source, _: &mut Reader<'_>) -> Result<SecretBytes, Error> {
+                Ok(b"hunter2".to_vec().into())
+            }
+        }
+
+        let reader = Reader::new()
+            .with_scheme("env", Fixed)
+            .with_scheme("file", Fixed);
+        let mut r = AsyncReader::from(reader);
+        let env = Source::Env("PASSARG_TEST_UNSET".into());
+        assert_eq!(assert_ok!(r.read_source(env).await), "hunter2");
+        let file = Source::File(path.clone());
+        assert_eq!(assert_ok!(r.read_source(file).await), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let timeout = std::time::Duration::from_millis(50);
+        // Polled (closed by the reader) and read on the blocking thread pool (left open).
+        for close_fds in [true, false] {
+            let (read_fd, write_fd) = pipe_with(b"h");
+            let reader = Reader::new().with_timeout(timeout);
+            let mut r = AsyncReader::from(reader.with_close_fds(close_fds));
+            let source = Source::Fd(read_fd);
+            assert!(matches!(
+                r.read_source(source.clone()).await,
+                Err(Error::Timeout { .. })
+            ));
+            // Nothing is lost to the read that timed out.
+            let written = unsafe { libc::write(write_fd, b"unter2\n".as_ptr().cast(), 7) };
+            assert_eq!(written, 7);
+            assert_eq!(assert_ok!(r.read_source(source).await), "hunter2");
+            unsafe { libc::close(write_fd) };
+            drop(r);
+            if !close_fds {
+                unsafe { libc::close(read_fd) };
+            }
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! [clap](https://docs.rs/clap) integration.
+//!
+//! With the `clap` feature enabled, [`Source`] implements
+//! [`ValueParserFactory`], so a `Source` field in a clap derive struct
+//! accepts arguments that are not valid UTF-8,
+//! its help lists the supported schemes,
+//! and parse errors never echo the argument, which may hold a literal password.
+//!
+//! [`PassInOut`] declares the usual pair of `--pass-in` and `--pass-out` options:
+//!
+//! ```rust
+//! use clap::Parser;
+//! use passarg::clap::{PassInOut, PassInOutNames};
+//!
+//! struct Names;
+//!
+//! impl PassInOutNames for Names {
+//!     const PASS_IN_DEFAULT: Option<&'static str> = Some("env:MY_PASS_IN");
+//!     const PASS_OUT_DEFAULT: Option<&'static str> = Some("env:MY_PASS_OUT");
+//! }
+//!
+//! #[derive(Parser)]
+//! struct Cli {
+//!     #[command(flatten)]
+//!     pass: PassInOut<Names>,
+//! }
+//!
+//! fn main() -> Result<(), passarg::Error> {
+//!     unsafe { // for doctest
+//!         std::env::set_var("MY_PASS_IN", "MyDecryptionPassphrase");
+//!         std::env::set_var("MY_PASS_OUT", "MyEncryptionPassphrase");
+//!     }
+//!     let cli = Cli::parse();
+//!     let (pass_in, pass_out) = cli.pass.read(&mut passarg::Reader::new())?;
+//!     // assert_eq!(pass_in.unwrap().expose(), "MyDecryptionPassphrase");
+//!     // assert_eq!(pass_out.unwrap().expose(), "MyEncryptionPassphrase");
+//!     // ...
+//!     Ok(())
+//! }
+//! ```
+
+use std::ffi::OsStr;
+use std::marker::PhantomData;
+
+use ::clap::builder::{PossibleValue, TypedValueParser, ValueParserFactory};
+use ::clap::error::ErrorKind;
+use ::clap::{Arg, ArgMatches, Args, Command, FromArgMatches, ValueHint};
+
+use crate::{Error, Reader, SecretString, Source};
+
+/// Schemes listed in help, with the argument each takes.
+const SCHEMES: &[(&str, &str)] = &[
+    (
+        "pass:",
+        "pass:PASSWORD, the password itself (visible to other users)",
+    ),
+    ("env:", "env:VAR, the environment variable VAR"),
+    ("file:", "file:PATH, the next line of the file PATH"),
+    (
+        "file-once:",
+        "file-once:PATH, like file:PATH, then removes the file",
+    ),
+    ("fd:", "fd:N, the next line of the file descriptor N"),
+    ("stdin", "the next line of standard input"),
+    ("prompt", "prompt[:TEXT], prompting on the terminal"),
+    (
+        "prompt-new",
+        "prompt-new[:TEXT], prompting twice on the terminal for a new password",
+    ),
+    ("exec:", "exec:COMMAND, the first line output by COMMAND"),
+    (
+        "sh:",
+        "sh:COMMAND, the first line output by COMMAND run with /bin/sh",
+    ),
+    (
+        "keyring:",
+        "keyring:KEYRING/NAME, the key NAME in a Linux kernel keyring",
+    ),
+    ("cred:", "cred:NAME, the systemd credential NAME"),
+    (
+        "keyfile:",
+        "keyfile:PATH, the entire file PATH as a binary key",
+    ),
+];
+
+/// Value parser for [`Source`], using [`Source::from_os_str()`].
+///
+/// Its possible values are the supported schemes,
+/// shown in help and offered in shell completions.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SourceValueParser;
+
+impl TypedValueParser for SourceValueParser {
+    type Value = Source;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, ::clap::Error> {
+        Source::from_os_str(value).map_err(|e| {
+            let arg = arg.map_or_else(|| "...".to_string(), ToString::to_string);
+            ::clap::Error::raw(
+                ErrorKind::ValueValidation,
+                format!("invalid value for '{arg}': {e}\n"),
+            )
+            .with_cmd(cmd)
+        })
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(
+            SCHEMES
+                .iter()
+                .map(|&(name, help)| PossibleValue::new(name).help(help)),
+        ))
+    }
+}
+
+impl ValueParserFactory for Source {
+    type Parser = SourceValueParser;
+
+    fn value_parser() -> Self::Parser {
+        SourceValueParser
+    }
+}
+
+/// Option names and default sources of [`PassInOut`].
+pub trait PassInOutNames {
+    /// Long option name of the input password, without the leading `--`.
+    const PASS_IN: &'static str = "pass-in";
+    /// Long option name of the output password, without the leading `--`.
+    const PASS_OUT: &'static str = "pass-out";
+    /// Source of the input password if the option is not given.
+    const PASS_IN_DEFAULT: Option<&'static str> = None;
+    /// Source of the output password if the option is not given.
+    const PASS_OUT_DEFAULT: Option<&'static str> = None;
+}
+
+/// The default [`PassInOutNames`]: `--pass-in` and `--pass-out`, without defaults.
+#[derive(Debug, Clone, Copy)]
+pub enum DefaultNames {}
+
+impl PassInOutNames for DefaultNames {}
+
+/// A pair of input and output password options, to be flattened into a clap parser.
+///
+/// `N` sets the option names and default sources.
+pub struct PassInOut<N: PassInOutNames = DefaultNames> {
+    /// Source of the input password, if given or defaulted.
+    pub pass_in: Option<Source>,
+    /// Source of the output password, if given or defaulted.
+    pub pass_out: Option<Source>,
+    names: PhantomData<fn() -> N>,
+}
+
+impl<N: PassInOutNames> PassInOut<N> {
+    /// Reads the input password, then the output password,
+    /// in the same order as OpenSSL.
+    pub fn read(
+        &self,
+        reader: &mut Reader,
+    ) -> Result<(Option<SecretString>, Option<SecretString>), Error> {
+        let mut read = |source: &Option<Source>| {
+            source
+                .clone()
+                .map(|source| reader.read_source_secret(source))
+                .transpose()
+        };
+        let pass_in = read(&self.pass_in)?;
+        let pass_out = read(&self.pass_out)?;
+        Ok((pass_in, pass_out))
+    }
+}
+
+impl<N: PassInOutNames> Clone for PassInOut<N> {
+    fn clone(&self) -> Self {
+        Self {
+            pass_in: self.pass_in.clone(),
+            pass_out: self.pass_out.clone(),
+            names: PhantomData,
+        }
+    }
+}
+
+impl<N: PassInOutNames> std::fmt::Debug for PassInOut<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PassInOut")
+            .field("pass_in", &self.pass_in)
+            .field("pass_out", &self.pass_out)
+            .finish()
+    }
+}
+
+fn source_arg(name: &'static str, default: Option<&'static str>, help: &'static str) -> Arg {
+    let arg = Arg::new(name)
+        .long(name)
+        .value_name("SPEC")
+        .value_parser(SourceValueParser)
+        .value_hint(ValueHint::Other)
+        .help(help);
+    match default {
+        Some(default) => arg.default_value(default),
+        None => arg,
+    }
+}
+
+impl<N: PassInOutNames> Args for PassInOut<N> {
+    fn augment_args(cmd: Command) -> Command {
+        cmd.arg(source_arg(
+            N::PASS_IN,
+            N::PASS_IN_DEFAULT,
+            "Source of the input password",
+        ))
+        .arg(source_arg(
+            N::PASS_OUT,
+            N::PASS_OUT_DEFAULT,
+            "Source of the output password",
+        ))
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl<N: PassInOutNames> FromArgMatches for PassInOut<N> {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, ::clap::Error> {
+        Self::from_arg_matches_mut(&mut matches.clone())
+    }
+
+    fn from_arg_matches_mut(matches: &mut ArgMatches) -> Result<Self, ::clap::Error> {
+        Ok(Self {
+            pass_in: matches.remove_one(N::PASS_IN),
+            pass_out: matches.remove_one(N::PASS_OUT),
+            names: PhantomData,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), ::clap::Error> {
+        self.update_from_arg_matches_mut(&mut matches.clone())
+    }
+
+    fn update_from_arg_matches_mut(
+        &mut self,
+        matches: &mut ArgMatches,
+    ) -> Result<(), ::clap::Error> {
+        if let Some(source) = matches.remove_one(N::PASS_IN) {
+            self.pass_in = Some(source);
+        }
+        if let Some(source) = matches.remove_one(N::PASS_OUT) {
+            self.pass_out = Some(source);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    use super::*;
+
+    #[test]
+    fn test_source_value_parser() {
+        let cmd = Command::new("test").arg(
+            Arg::new("pass")
+                .long("pass")
+                .value_parser(SourceValueParser),
+        );
+        let path = OsString::from_vec(b"file:/tmp/\xff".to_vec());
+        let matches = cmd
+            .clone()
+            .try_get_matches_from([OsString::from("test"), "--pass".into(), path])
+            .unwrap();
+        assert_eq!(
+            matches.get_one::<Source>("pass"),
+            Some(&Source::File(
+                OsString::from_vec(b"/tmp/\xff".to_vec()).into()
+            ))
+        );
+        let e = cmd
+            .try_get_matches_from(["test", "--pass", "omg:hunter2"])
+            .unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ValueValidation);
+        assert!(!e.to_string().contains("hunter2"));
+    }
+
+    #[test]
+    fn test_pass_in_out() {
+        enum Names {}
+
+        impl PassInOutNames for Names {
+            const PASS_IN: &'static str = "passin";
+            const PASS_IN_DEFAULT: Option<&'static str> = Some("pass:omg");
+        }
+
+        let cmd = PassInOut::<Names>::augment_args(Command::new("test"));
+        let parse = |args: &[&str]| {
+            let mut matches = cmd
+                .clone()
+                .try_get_matches_from(std::iter::once("test").chain(args.iter().copied()))
+                .unwrap();
+            PassInOut::<Names>::from_arg_matches_mut(&mut matches).unwrap()
+        };
+        let pass = parse(&[]);
+        assert_eq!(pass.pass_in, Some(Source::Pass("omg".into())));
+        assert_eq!(pass.pass_out, None);
+        let pass = parse(&["--passin", "stdin", "--pass-out", "pass:wtf"]);
+        assert_eq!(pass.pass_in, Some(Source::Stdin));
+        assert_eq!(pass.pass_out, Some(Source::Pass("wtf".into())));
+
+        let pass = parse(&["--passin", "pass:in", "--pass-out", "pass:out"]);
+        let (pass_in, pass_out) = pass.read(&mut Reader::new()).unwrap();
+        assert_eq!(pass_in.unwrap().expose(), "in");
+        assert_eq!(pass_out.unwrap().expose(), "out");
+
+        let help = cmd.clone().render_long_help().to_string();
+        for (scheme, _) in SCHEMES {
+            assert!(help.contains(scheme), "{scheme} missing from {help}");
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use std::env;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+/// Removes `var` from the environment, like [`env::remove_var()`].
+///
+/// Does nothing if `var` is not a valid variable name, instead of panicking.
+pub(crate) fn remove(var: &OsStr) {
+    let name = var.as_bytes();
+    if name.is_empty() || name.contains(&b'=') || name.contains(&0) {
+        return;
+    }
+    env::remove_var(var);
+}
@@ -0,0 +1,310 @@
+use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use crate::Source;
+
+/// Errors that can arise while reading password argument.
+///
+/// Every error names the offending argument,
+/// with any literal password redacted, as in
+/// `cannot read password from file:/x: permission denied`.
+/// Use [`Error::kind()`] to match errors programmatically.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The password argument `spec` is malformed.
+    #[error("invalid password argument {spec}: {reason}")]
+    InvalidSpec { spec: String, reason: String },
+    /// Reading from the source failed.
+    #[error("cannot read password from {spec}: {}", describe_io(.error))]
+    Io {
+        spec: Source,
+        #[source]
+        error: io::Error,
+    },
+    /// The environment variable is not set, or not valid Unicode.
+    #[error("cannot read password from {spec}: {error}")]
+    EnvVar {
+        spec: Source,
+        #[source]
+        error: env::VarError,
+    },
+    /// The password is not valid UTF-8.
+    #[error("cannot read password from {spec}: password is not valid UTF-8")]
+    InvalidUtf8 { spec: Source },
+    /// The command exited unsuccessfully.
+    #[error("cannot read password from {spec}: command failed with {status}: {stderr}")]
+    Command {
+        spec: Source,
+        status: ExitStatus,
+        stderr: String,
+    },
+    /// The key does not exist in the keyring.
+    #[error("cannot read password from {spec}: key not found")]
+    KeyNotFound { spec: Source },
+    /// The process is not allowed to read the key.
+    #[error("cannot read password from {spec}: permission denied")]
+    KeyPermissionDenied { spec: Source },
+    /// `${CREDENTIALS_DIRECTORY}` is not set.
+    #[error("cannot read password from {spec}: no credentials directory; not running as a service with credentials")]
+    NoCredentialsDirectory { spec: Source },
+    /// No source in the chain supplied a password.
+    #[error("cannot read password from {spec}: no source in chain supplied a password ({})", join_errors(.errors))]
+    Chain { spec: Source, errors: Vec<Error> },
+    /// The two entries of a confirming prompt did not match too many times.
+    #[error("cannot read password from {spec}: passwords do not match")]
+    Mismatch { spec: Source },
+    /// The file-like source has no more lines.
+    #[error("cannot read password from {spec}: unexpected end of file")]
+    UnexpectedEof { spec: Source },
+    /// The keyfile is larger than the limit set by
+    /// [`Reader::with_keyfile_limit()`](crate::Reader::with_keyfile_limit).
+    #[error("cannot read password from {spec}: file is larger than {limit} bytes")]
+    TooLarge { spec: Source, limit: u64 },
+    /// The source is forbidden by the [`Policy`](crate::Policy) of the reader.
+    #[error("cannot read password from {spec}: forbidden by policy: {reason}")]
+    PolicyViolation { spec: Source, reason: String },
+    /// The file is not private enough to hold a password;
+    /// see [`PermissionCheck`](crate::PermissionCheck).
+    /// `path` is the file, or the directory holding a symbolic link on its path,
+    /// and `mode` its permission bits.
+    #[error("cannot read password from {spec}: insecure permissions: {} (mode {mode:04o}) is {reason}", .path.display())]
+    InsecureFile {
+        spec: Source,
+        path: PathBuf,
+        mode: u32,
+        reason: String,
+    },
+    /// No password was read within the timeout set by
+    /// [`Reader::with_timeout()`](crate::Reader::with_timeout).
+    #[error("cannot read password from {spec}: timed out after {timeout:?}")]
+    Timeout { spec: Source, timeout: Duration },
+    /// A [`SourceProvider`](crate::SourceProvider) failed to read the password.
+    #[error("cannot read password from {spec}: {error}")]
+    Provider {
+        spec: Source,
+        #[source]
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The file of a **file-once:** source could not be removed after reading;
+    /// the password itself was read.
+    /// Reported by [`Reader::finish()`](crate::Reader::finish).
+    #[error("cannot remove {spec} after reading the password: {}", describe_io(.error))]
+    Unlink {
+        spec: Source,
+        #[source]
+        error: io::Error,
+    },
+    /// No slot of this name was registered with
+    /// [`Reader::register_slot()`](crate::Reader::register_slot).
+    #[error("unknown password slot {name:?}")]
+    UnknownSlot { name: String },
+    /// A slot of this name is already registered.
+    #[error("password slot {name:?} is already registered")]
+    DuplicateSlot { name: String },
+    /// The slot was already read.
+    #[error("password slot {name:?} was already read")]
+    SlotAlreadyRead { name: String },
+}
+
+/// Kind of an [`Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The password argument is malformed.
+    InvalidSpec,
+    /// The source does not exist,
+    /// e.g. an unset environment variable, or a missing file or key.
+    NotFound,
+    /// The process is not allowed to read from the source.
+    PermissionDenied,
+    /// The file-like source has no more lines.
+    UnexpectedEof,
+    /// The password is not valid UTF-8.
+    InvalidUtf8,
+    /// The command exited unsuccessfully.
+    CommandFailed,
+    /// The two entries of a confirming prompt did not match.
+    Mismatch,
+    /// The keyfile is too large.
+    TooLarge,
+    /// The slot is unknown, already registered, or already read.
+    InvalidSlot,
+    /// The source is forbidden by policy.
+    PolicyViolation,
+    /// The file is accessible by others.
+    InsecureFile,
+    /// No password was read in time.
+    TimedOut,
+    /// Any other error, typically an I/O error.
+    Other,
+}
+
+impl Error {
+    /// Returns the kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::InvalidSpec { .. } => ErrorKind::InvalidSpec,
+            Error::Io { error, .. } => match error.kind() {
+                io::ErrorKind::NotFound => ErrorKind::NotFound,
+                io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+                _ => ErrorKind::Other,
+            },
+            Error::EnvVar {
+                error: env::VarError::NotPresent,
+                ..
+            } => ErrorKind::NotFound,
+            Error::EnvVar { .. } | Error::InvalidUtf8 { .. } => ErrorKind::InvalidUtf8,
+            Error::Command { .. } => ErrorKind::CommandFailed,
+            Error::KeyNotFound { .. } | Error::NoCredentialsDirectory { .. } => ErrorKind::NotFound,
+            Error::KeyPermissionDenied { .. } => ErrorKind::PermissionDenied,
+            Error::Chain { .. } => ErrorKind::NotFound,
+            Error::Mismatch { .. } => ErrorKind::Mismatch,
+            Error::UnexpectedEof { .. } => ErrorKind::UnexpectedEof,
+            Error::TooLarge { .. } => ErrorKind::TooLarge,
+            Error::PolicyViolation { .. } => ErrorKind::PolicyViolation,
+            Error::InsecureFile { .. } => ErrorKind::InsecureFile,
+            Error::Timeout { .. } => ErrorKind::TimedOut,
+            Error::Provider { .. } => ErrorKind::Other,
+            Error::Unlink { error, .. } if error.kind() == io::ErrorKind::PermissionDenied => {
+                ErrorKind::PermissionDenied
+            }
+            Error::Unlink { .. } => ErrorKind::Other,
+            Error::UnknownSlot { .. }
+            | Error::DuplicateSlot { .. }
+            | Error::SlotAlreadyRead { .. } => ErrorKind::InvalidSlot,
+        }
+    }
+
+    /// Returns the source that failed,
+    /// or `None` if the password argument could not be parsed
+    /// or the error is about a slot.
+    pub fn spec(&self) -> Option<&Source> {
+        match self {
+            Error::InvalidSpec { .. }
+            | Error::UnknownSlot { .. }
+            | Error::DuplicateSlot { .. }
+            | Error::SlotAlreadyRead { .. } => None,
+            Error::Io { spec, .. }
+            | Error::EnvVar { spec, .. }
+            | Error::InvalidUtf8 { spec }
+            | Error::Command { spec, .. }
+            | Error::KeyNotFound { spec }
+            | Error::KeyPermissionDenied { spec }
+            | Error::NoCredentialsDirectory { spec }
+            | Error::Chain { spec, .. }
+            | Error::Mismatch { spec }
+            | Error::UnexpectedEof { spec }
+            | Error::TooLarge { spec, .. }
+            | Error::PolicyViolation { spec, .. }
+            | Error::InsecureFile { spec, .. }
+            | Error::Timeout { spec, .. }
+            | Error::Provider { spec, .. }
+            | Error::Unlink { spec, .. } => Some(spec),
+        }
+    }
+
+    /// Returns whether this error means the source had no password,
+    /// as opposed to it failing to read,
+    /// in which case a [`Source::Chain`] falls back to its next link.
+    ///
+    /// These are errors of kind [`ErrorKind::NotFound`]
+    /// and [`ErrorKind::UnexpectedEof`].
+    pub fn is_not_found(&self) -> bool {
+        matches!(self.kind(), ErrorKind::NotFound | ErrorKind::UnexpectedEof)
+    }
+
+    /// Turns an I/O error that timed out into [`Error::Timeout`].
+    pub(crate) fn with_timeout(self, timeout: Duration) -> Self {
+        match self {
+            Error::Io { spec, error } if error.kind() == io::ErrorKind::TimedOut => {
+                Error::Timeout { spec, timeout }
+            }
+            e => e,
+        }
+    }
+
+    pub(crate) fn invalid_spec(spec: &str, reason: impl Into<String>) -> Self {
+        Error::InvalidSpec {
+            spec: spec.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Attaches the offending source to lower-level errors.
+pub(crate) trait ResultExt<T> {
+    fn with_spec(self, spec: &Source) -> Result<T, Error>;
+}
+
+impl<T> ResultExt<T> for Result<T, io::Error> {
+    fn with_spec(self, spec: &Source) -> Result<T, Error> {
+        self.map_err(|error| Error::Io {
+            spec: spec.clone(),
+            error,
+        })
+    }
+}
+
+impl<T> ResultExt<T> for Result<T, env::VarError> {
+    fn with_spec(self, spec: &Source) -> Result<T, Error> {
+        self.map_err(|error| Error::EnvVar {
+            spec: spec.clone(),
+            error,
+        })
+    }
+}
+
+/// Describes `error` without the `(os error N)` suffix,
+/// starting in lower case like the rest of our messages.
+fn describe_io(error: &io::Error) -> String {
+    let mut message = error.to_string();
+    if let Some(code) = error.raw_os_error() {
+        if let Some(stripped) = message.strip_suffix(&format!(" (os error {code})")) {
+            message.truncate(stripped.len());
+        }
+    }
+    if let Some(first) = message.get(..1) {
+        let lower = first.to_ascii_lowercase();
+        message.replace_range(..1, &lower);
+    }
+    message
+}
+
+fn join_errors(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let spec = Source::File("/x".into());
+        let error = Err::<(), _>(io::Error::from_raw_os_error(libc::EACCES))
+            .with_spec(&spec)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(error.spec(), Some(&spec));
+        assert_eq!(
+            error.to_string(),
+            "cannot read password from file:/x: permission denied"
+        );
+        let error = Error::Mismatch {
+            spec: Source::Pass("hunter2".into()),
+        };
+        assert_eq!(
+            error.to_string(),
+            "cannot read password from pass:<redacted>: passwords do not match"
+        );
+        assert!(!format!("{error:?}").contains("hunter2"));
+    }
+}
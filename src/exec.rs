@@ -0,0 +1,149 @@
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::error::ResultExt;
+use crate::line::{LineOptions, LineReader};
+use crate::{Error, Source};
+
+/// Splits `s` into words like a POSIX shell would,
+/// honoring single quotes, double quotes and backslash escapes,
+/// but performing no expansion.
+/// Returns `None` if `s` has an unterminated quote or a trailing backslash.
+pub(crate) fn split_command_line(s: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            c @ ('"' | '\\' | '$' | '`') => word.push(c),
+                            c => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).push(chars.next()?),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Some(words)
+}
+
+/// Joins `words` into a command line that [`split_command_line()`] splits back.
+pub(crate) fn join_command_line(words: &[String]) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c);
+    words
+        .iter()
+        .map(|word| {
+            if !word.is_empty() && word.chars().all(safe) {
+                word.clone()
+            } else {
+                format!("'{}'", word.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Runs `cmd` and returns the first line of its standard output,
+/// including the line ending if any.
+///
+/// Standard input is inherited.
+/// The rest of the standard output is read and discarded,
+/// and the standard error is collected for [`Error::Command`]
+/// in case the command fails.
+pub(crate) fn read_first_line(
+    mut cmd: Command,
+    options: &LineOptions,
+    spec: &Source,
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let mut child = cmd
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_spec(spec)?;
+    let mut stderr = child.stderr.take().unwrap();
+    let stderr = thread::spawn(move || {
+        let mut buf = Vec::new();
+        stderr.read_to_end(&mut buf).map(|_| buf)
+    });
+    let mut stdout = LineReader::new(child.stdout.take().unwrap());
+    let mut line = Zeroizing::new(Vec::new());
+    let result = stdout.read_line(&mut line, options).and_then(|_| {
+        let mut rest = Zeroizing::new(Vec::new());
+        while stdout.read_line(&mut rest, options)? != 0 {
+            rest.zeroize();
+        }
+        Ok(())
+    });
+    drop(stdout);
+    let status = child.wait().with_spec(spec)?;
+    let stderr = stderr
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("stderr reader panicked")))
+        .with_spec(spec)?;
+    result.with_spec(spec)?;
+    if !status.success() {
+        return Err(Error::Command {
+            spec: spec.clone(),
+            status,
+            stderr: String::from_utf8_lossy(&stderr).trim_end().into(),
+        });
+    }
+    Ok(line)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_command_line() {
+        let split = |s| split_command_line(s).unwrap();
+        assert_eq!(
+            split("/usr/bin/pass show db"),
+            ["/usr/bin/pass", "show", "db"]
+        );
+        assert_eq!(split("  a   b "), ["a", "b"]);
+        assert_eq!(
+            split(r#"a 'b c' "d \"e\"" f\ g ''"#),
+            ["a", "b c", "d \"e\"", "f g", ""]
+        );
+        assert_eq!(split(r#""a\b""#), [r"a\b"]);
+        assert!(split("").is_empty());
+        assert_eq!(split_command_line("'a"), None);
+        assert_eq!(split_command_line("\"a"), None);
+        assert_eq!(split_command_line("a\\"), None);
+    }
+
+    #[test]
+    fn test_join_command_line() {
+        let words = ["pass", "show", "it's db", "", "a\"b"].map(String::from);
+        let joined = join_command_line(&words);
+        assert_eq!(joined, r#"pass show 'it'\''s db' '' 'a"b'"#);
+        assert_eq!(split_command_line(&joined).unwrap(), words);
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use crate::error::ResultExt;
+use crate::line::{file_id, path_id, FileId, LineReader};
+use crate::once::OnceFiles;
+use crate::{check_fd_number, fd_path, Error, Settings, Source};
+
+/// Open file that a reader reads the lines of a file-like source from.
+pub(crate) trait FileStream: Sized {
+    /// Wraps a file opened by path.
+    fn from_file(file: File) -> io::Result<Self>;
+
+    /// Wraps `fd`, closing it when dropped if `owned`.
+    fn from_fd(fd: RawFd, owned: bool) -> io::Result<Self>;
+
+    /// Returns whether reading ahead is allowed,
+    /// that is, whether nothing but the reader reads the file.
+    fn is_private(&self) -> bool;
+}
+
+/// What a file-like source reads.
+pub(crate) enum Target<'a> {
+    /// A file to open by path.
+    Path(&'a Path),
+    /// A file descriptor, closed by the reader if owned.
+    Fd(RawFd, bool),
+}
+
+impl<'a> Target<'a> {
+    /// Returns what `source` reads, or `None` if it is not file-like;
+    /// **fd:** descriptors other than standard input are owned if `close_fds`.
+    pub(crate) fn of(source: &'a Source, close_fds: bool) -> Option<Self> {
+        Some(match source {
+            // The program keeps the descriptor that a path names open.
+            Source::File(path) | Source::FileOnce(path) => match fd_path(path) {
+                Some(fd) => Target::Fd(fd, false),
+                None => Target::Path(path),
+            },
+            &Source::Fd(fd) => Target::Fd(fd, fd != 0 && close_fds),
+            Source::Stdin => Target::Fd(0, false),
+            _ => return None,
+        })
+    }
+}
+
+/// Result of [`Files::find_file()`].
+pub(crate) enum Found {
+    /// The stream already open for the path.
+    Open(FileId),
+    /// The canonical path to open.
+    Closed(PathBuf),
+}
+
+/// Streams of lines of the file-like sources of a reader.
+///
+/// Sources that name the same open file share one stream;
+/// the files of **file-once:** sources are removed once read.
+pub(crate) struct Files<S> {
+    streams: HashMap<FileId, LineReader<S>>,
+    paths: HashMap<PathBuf, FileId>,
+    once: OnceFiles,
+}
+
+impl<S> Default for Files<S> {
+    fn default() -> Self {
+        Self {
+            streams: HashMap::new(),
+            paths: HashMap::new(),
+            once: OnceFiles::default(),
+        }
+    }
+}
+
+impl<S: FileStream> Files<S> {
+    /// Returns the stream `id`, as returned when it was opened.
+    pub(crate) fn stream(&mut self, id: FileId) -> &mut LineReader<S> {
+        self.streams.get_mut(&id).expect("stream is open")
+    }
+
+    /// Looks up the stream of `path`, which must be allowed by the policy of `settings`.
+    pub(crate) fn find_file(
+        &self,
+        path: &Path,
+        settings: &Settings,
+        source: &Source,
+    ) -> Result<Found, Error> {
+        let canonical = match std::fs::canonicalize(path) {
+            Ok(canonical) => canonical,
+            // Removed after reading, by a **file-once:** source.
+            Err(e) => {
+                return self
+                    .once
+                    .removed(path)
+                    .map(Found::Open)
+                    .ok_or(e)
+                    .with_spec(source)
+            }
+        };
+        settings.policy.check_file_dir(&canonical, source)?;
+        // Reopening could block (a FIFO whose writer has gone) or rewind the file.
+        Ok(match self.paths.get(&canonical) {
+            Some(&id) => Found::Open(id),
+            None => Found::Closed(canonical),
+        })
+    }
+
+    /// Adds the stream of `file`, opened at `canonical` for `path`,
+    /// if its permissions pass the check of `settings`.
+    pub(crate) fn insert_file(
+        &mut self,
+        path: &Path,
+        canonical: PathBuf,
+        file: File,
+        settings: &Settings,
+        source: &Source,
+    ) -> Result<FileId, Error> {
+        settings.check_permissions(path, &file, source)?;
+        let id = file_id(file.as_raw_fd()).with_spec(source)?;
+        if let Entry::Vacant(entry) = self.streams.entry(id) {
+            entry.insert(LineReader::new(S::from_file(file).with_spec(source)?));
+        }
+        self.paths.insert(canonical, id);
+        Ok(id)
+    }
+
+    /// Opens the stream of `fd` if not yet open, closing it when dropped if `owned`.
+    pub(crate) fn open_fd(
+        &mut self,
+        fd: RawFd,
+        owned: bool,
+        source: &Source,
+    ) -> Result<FileId, Error> {
+        check_fd_number(fd).map_err(|reason| Error::invalid_spec(&source.to_string(), reason))?;
+        let id = file_id(fd).with_spec(source)?;
+        if let Entry::Vacant(entry) = self.streams.entry(id) {
+            let stream = S::from_fd(fd, owned).with_spec(source)?;
+            // Leave unread bytes in files that the program keeps reading.
+            entry.insert(if stream.is_private() {
+                LineReader::new(stream)
+            } else {
+                LineReader::with_capacity(1, stream)
+            });
+        }
+        Ok(id)
+    }
+
+    /// Returns the ID of the stream that a file-like source reads,
+    /// without opening anything, which could block (a FIFO)
+    /// or take over a file descriptor.
+    pub(crate) fn stream_id(&self, source: &Source) -> Option<FileId> {
+        let fd = match Target::of(source, false)? {
+            Target::Path(path) => {
+                let Ok(canonical) = std::fs::canonicalize(path) else {
+                    return self.once.removed(path);
+                };
+                return match self.paths.get(&canonical) {
+                    Some(&id) => Some(id),
+                    None => path_id(&canonical).ok(),
+                };
+            }
+            Target::Fd(fd, _) => fd,
+        };
+        check_fd_number(fd).ok()?;
+        file_id(fd).ok()
+    }
+
+    /// Removes the file of a **file-once:** `source` once read through the stream `id`.
+    pub(crate) fn remove_once(&mut self, source: &Source, id: FileId) {
+        let Source::FileOnce(path) = source else {
+            return;
+        };
+        if fd_path(path).is_none() && self.once.remove(path, id, source) {
+            // A new file at the same path is a different file.
+            self.paths.retain(|_, &mut v| v != id);
+        }
+    }
+
+    /// Same as [`OnceFiles::finish()`].
+    pub(crate) fn finish(&mut self) -> Result<(), Error> {
+        self.once.finish()
+    }
+}
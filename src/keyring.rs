@@ -0,0 +1,136 @@
+use std::io;
+use zeroize::Zeroizing;
+
+use crate::{Error, Source};
+
+/// Resolves a keyring name to its keyring ID.
+///
+/// Accepts the special keyring names `thread`, `process`, `session`, `user`
+/// and `user-session`, their `keyctl(1)` shorthands `@t`, `@p`, `@s`, `@u` and `@us`,
+/// as well as numeric keyring IDs.
+pub(crate) fn keyring_id(name: &str) -> Option<i32> {
+    Some(match name {
+        "thread" | "@t" => -1,
+        "process" | "@p" => -2,
+        "session" | "@s" => -3,
+        "user" | "@u" => -4,
+        "user-session" | "@us" => -5,
+        id => id.parse().ok().filter(|&id| id > 0)?,
+    })
+}
+
+/// Searches `keyring` (and the keyrings linked to it)
+/// for a `user` key named `description`, and returns the key's payload.
+pub(crate) fn read_key(
+    keyring: i32,
+    description: &str,
+    spec: &Source,
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    read_key_payload(keyring, description).map_err(|error| match error.raw_os_error() {
+        #[cfg(target_os = "linux")]
+        Some(libc::ENOKEY | libc::EKEYEXPIRED | libc::EKEYREVOKED) => {
+            Error::KeyNotFound { spec: spec.clone() }
+        }
+        Some(libc::EACCES) => Error::KeyPermissionDenied { spec: spec.clone() },
+        _ => Error::Io {
+            spec: spec.clone(),
+            error,
+        },
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn read_key_payload(keyring: i32, description: &str) -> io::Result<Zeroizing<Vec<u8>>> {
+    use std::ffi::CString;
+    use zeroize::Zeroize;
+
+    const KEYCTL_SEARCH: libc::c_long = 10;
+    const KEYCTL_READ: libc::c_long = 11;
+
+    let description =
+        CString::new(description).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let key = unsafe {
+        libc::syscall(
+            libc::SYS_keyctl,
+            KEYCTL_SEARCH,
+            keyring as libc::c_long,
+            c"user".as_ptr(),
+            description.as_ptr(),
+            0 as libc::c_long,
+        )
+    };
+    if key < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut payload = Zeroizing::new(vec![0u8; 256]);
+    loop {
+        let n = unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                KEYCTL_READ,
+                key,
+                payload.as_mut_ptr(),
+                payload.len(),
+            )
+        };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let n = n as usize;
+        if n <= payload.len() {
+            payload.truncate(n);
+            return Ok(payload);
+        }
+        // The key grew larger than our buffer; retry with a large enough one.
+        payload.zeroize();
+        payload.resize(n, 0);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_key_payload(_keyring: i32, _description: &str) -> io::Result<Zeroizing<Vec<u8>>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "kernel keyrings are only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    #[test]
+    fn test_keyring_id() {
+        assert_eq!(keyring_id("user"), Some(-4));
+        assert_eq!(keyring_id("@s"), Some(-3));
+        assert_eq!(keyring_id("123"), Some(123));
+        assert_eq!(keyring_id("-4"), None);
+        assert_eq!(keyring_id("omg"), None);
+    }
+
+    /// Adds a key to the process keyring,
+    /// returning `false` if keyrings are unavailable,
+    /// as in containers whose seccomp profile blocks `add_key(2)`.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn add_process_key(description: &str, payload: &[u8]) -> bool {
+        let description = std::ffi::CString::new(description).unwrap();
+        let key = unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                c"user".as_ptr(),
+                description.as_ptr(),
+                payload.as_ptr(),
+                payload.len(),
+                keyring_id("process").unwrap() as libc::c_long,
+            )
+        };
+        if key > 0 {
+            return true;
+        }
+        let error = io::Error::last_os_error();
+        match error.raw_os_error() {
+            Some(libc::EPERM | libc::ENOSYS) => false,
+            _ => panic!("add_key: {error}"),
+        }
+    }
+}
@@ -22,10 +22,10 @@
 //!     }
 //!     let cli = Cli::parse();
 //!     let mut r = passarg::Reader::new();
-//!     let pass_in = r.read_pass_arg(&cli.pass_in)?;
-//!     let pass_out = r.read_pass_arg(&cli.pass_out)?;
-//!     // assert_eq!(pass_in, "MyDecryptionPassphrase");
-//!     // assert_eq!(pass_out, "MyEncryptionPassphrase");
+//!     let pass_in = r.read_pass_arg_secret(&cli.pass_in)?;
+//!     let pass_out = r.read_pass_arg_secret(&cli.pass_out)?;
+//!     // assert_eq!(pass_in.expose(), "MyDecryptionPassphrase");
+//!     // assert_eq!(pass_out.expose(), "MyEncryptionPassphrase");
 //!     // ...
 //!     Ok(())
 //! }
@@ -54,6 +54,8 @@
 //!   Since the environment of other processes is visible on certain platforms
 //!   (e.g. ps under certain Unix OSes)
 //!   this option should be used with caution.
+//!   [`Reader::with_remove_env()`] removes the variable once read,
+//!   so that child processes do not inherit it.
 //!
 //! * **file**:*pathname*
 //!
@@ -66,6 +68,16 @@
 //!   the first line will be used for the input password,
 //!   and the next line will be used for the output password.
 //!
+//!   Reading past the last line fails with [`Error::UnexpectedEof`],
+//!   as it does with OpenSSL;
+//!   see [`Reader::with_eof_as_empty()`] to get an empty password instead.
+//!
+//!   Like OpenSSL, passarg by default only recognizes LF (`\n`) as line ending,
+//!   so that a CR (`\r`) before it is part of the password,
+//!   and does not remove a UTF-8 byte order mark (BOM) at the start of the file.
+//!   See [`Reader::with_line_ending()`] and [`Reader::with_strip_bom()`]
+//!   to handle files written on Windows or by editors that add a BOM.
+//!
 //! * **fd**:*number*
 //!
 //!   Reads the password from the file descriptor *number*.
@@ -73,6 +85,12 @@
 //!   The same line handling as described for **file:** applies
 //!   to passwords read from file descriptors.
 //!
+//!   **fd:0** reads standard input, like **stdin**.
+//!   **fd:1** and **fd:2** (standard output and error) are rejected,
+//!   as is a *number* that is not an open file descriptor.
+//!   The file descriptor is closed when the [`Reader`] goes out of scope,
+//!   unless disabled with [`Reader::with_close_fds()`].
+//!
 //!   **fd:** is not supported on Windows.
 //!
 //! * **stdin**
@@ -89,6 +107,162 @@
 //!   If *text* is given, it is used as the prompt.
 //!   Otherwise, `Password: ` is used.
 //!
+//! * **prompt-new**\[:*text*]
+//!
+//!   Same as **prompt:**, but prompts twice,
+//!   the second time with `Verifying - ` prepended to the prompt,
+//!   and asks again if the two entries do not match.
+//!   This is intended for a new password, such as for **-passout**.
+//!   If *text* is not given, `New password: ` is used.
+//!   After too many mismatches
+//!   (see [`Reader::with_prompt_retries()`]), [`Error::Mismatch`] is returned.
+//!
+//! * **exec**:*command*, **cmd**:*command*
+//!
+//!   Runs *command* and reads the password
+//!   from the first line of its standard output.
+//!   *command* is split into words like a shell would,
+//!   honoring quotes and backslashes, but is not run by a shell;
+//!   the first word is the program to run.
+//!   Standard input is inherited from the calling process.
+//!   The command must exit successfully;
+//!   otherwise its standard error is reported in [`Error::Command`].
+//!
+//! * **sh**:*command*
+//!
+//!   Same as **exec:**, but runs *command* with `/bin/sh -c`.
+//!
+//! * **keyring**:*keyring*/*description*, **keyctl**:*keyring*/*description*
+//!
+//!   Reads the password from the payload of the `user` key *description*,
+//!   found by searching the Linux kernel keyring *keyring*
+//!   (and the keyrings linked to it), with a trailing newline removed.
+//!   *keyring* is one of `thread`, `process`, `session`, `user` or `user-session`,
+//!   their `keyctl(1)` shorthands `@t`, `@p`, `@s`, `@u` or `@us`,
+//!   or a numeric keyring ID.
+//!   A missing key is reported as [`Error::KeyNotFound`],
+//!   and a key that the process may not read as [`Error::KeyPermissionDenied`].
+//!
+//!   **keyring:** is only supported on Linux.
+//!
+//! * **cred**:*name*
+//!
+//!   Reads the password from the systemd credential *name*,
+//!   i.e. the file *name* in the directory `${CREDENTIALS_DIRECTORY}`
+//!   set up by `LoadCredential=` and similar service settings.
+//!   Only the first line, up to the newline character, is read by default;
+//!   see [`Reader::with_credential_mode()`] to read the entire credential instead.
+//!   Unlike with **file:**, each **cred:** argument reads the credential afresh.
+//!   If `${CREDENTIALS_DIRECTORY}` is not set,
+//!   [`Error::NoCredentialsDirectory`] is returned.
+//!
+//! * **file-once**:*pathname*
+//!
+//!   Reads the password like **file:**, then removes the file *pathname*,
+//!   for a password handed over in a file that should not outlive its use,
+//!   such as one on a tmpfs written by an orchestrator.
+//!   The file is removed once its first line is read;
+//!   its later lines can still be read by further **file-once:** or **file:** arguments
+//!   for the same *pathname*, from the stream already open.
+//!   A failure to remove the file does not fail the read:
+//!   [`Reader::finish()`] retries and reports it with [`Error::Unlink`],
+//!   and dropping the [`Reader`] retries it too.
+//!
+//! * **keyfile**:*pathname*
+//!
+//!   Reads the entire file *pathname* as is, as a binary key,
+//!   typically with [`Reader::read_source_bytes()`].
+//!   Unlike with **file:**, nothing is removed from the content,
+//!   and each **keyfile:** argument reads the file afresh.
+//!   Files larger than 8 MiB fail with [`Error::TooLarge`];
+//!   see [`Reader::with_keyfile_limit()`] to change the limit.
+//!
+//! # Fallback Chains
+//!
+//! Multiple arguments can be chained with `||`, as in
+//! `env:APP_PASS||file:/run/secrets/app||prompt`,
+//! to read the password from the first of them that exists.
+//! A source is skipped over if it does not exist,
+//! e.g. an unset environment variable, a missing file or key,
+//! or a missing credentials directory (see [`Error::is_not_found()`]);
+//! any other error, such as a permission error, ends the chain.
+//! If no source in the chain exists, [`Error::Chain`] is returned.
+//! [`Reader::read_source_with_origin()`] tells which source supplied the password.
+//!
+//! Since `||` always separates chained arguments,
+//! it cannot appear in a **pass:** password or in other arguments.
+//!
+//! # Timeouts
+//!
+//! By default, reading a pipe whose writer never writes, or a prompt nobody answers, waits forever.
+//! [`Reader::with_timeout()`] limits how long each read from **file:**, **fd:** and **stdin**,
+//! and each prompt entry, may take, after which it fails with [`Error::Timeout`].
+//! A timeout ends a chain like any other error.
+//!
+//! # Custom Schemes
+//!
+//! Applications can add their own schemes, such as **vault:** for an in-house secret store,
+//! by registering a [`SourceProvider`] with [`Reader::with_scheme()`].
+//! [`Reader::read_pass_arg()`] and [`Reader::parse_pass_arg()`] then accept its arguments,
+//! also as links of a chain, as [`Source::Custom`] sources.
+//! Registering the name of a built-in scheme replaces it, also for [`Source`] values
+//! parsed on their own, as with [`str::parse()`] or the [`clap`] value parser:
+//! those are read through the provider registered for the scheme they display with,
+//! such as `exec` for [`Source::Exec`].
+//! A [`Policy`] treats all custom sources as [`SourceKind::Custom`].
+//!
+//! # Source Policy
+//!
+//! A [`Policy`] set with [`Reader::with_policy()`] restricts the sources read,
+//! for example to forbid **pass:** and **env:** in production:
+//!
+//! ```rust
+//! use passarg::{Policy, SourceKind};
+//!
+//! let policy = Policy::new()
+//!     .deny([SourceKind::Pass, SourceKind::Env])
+//!     .require_file_dir("/run/secrets");
+//! let mut r = passarg::Reader::new().with_policy(policy);
+//! assert!(r.read_pass_arg("pass:hunter2").is_err());
+//! ```
+//!
+//! A forbidden source fails with [`Error::PolicyViolation`] before anything is read;
+//! in a chain, every link must be allowed.
+//!
+//! Like `ssh(1)` with private keys, [`Reader::with_permission_check()`] can check that
+//! **file:**, **file-once:** and **keyfile:** sources are private:
+//! not accessible by group or others, owned by the user (or root),
+//! and not reached through a symbolic link in a directory that others can write to.
+//! [`PermissionCheck::Strict`] fails with [`Error::InsecureFile`], naming the insecure path and its mode;
+//! [`PermissionCheck::Warn`] reports it to a callback set with
+//! [`Reader::with_permission_warning()`] (by default, printing to standard error),
+//! and reads the file anyway.
+//!
+//! # Non-UTF-8 Arguments
+//!
+//! Environment variable names and paths need not be valid UTF-8.
+//! [`Source::from_os_str()`] parses such arguments, e.g. from [`std::env::args_os()`],
+//! and [`Source::to_spec_os_string_unredacted()`] turns them back into arguments losslessly.
+//!
+//! # clap Integration
+//!
+//! With the `clap` feature enabled, the [`clap`] module integrates with clap.
+//! [`Source`] can be used directly as the type of a clap argument:
+//! [`clap::SourceValueParser`] parses it with [`Source::from_os_str()`],
+//! lists the supported schemes in help,
+//! and never includes the argument, which may hold a literal password, in errors.
+//! [`clap::PassInOut`] declares a pair of `--pass-in` and `--pass-out` options,
+//! whose names and default sources can be changed with [`clap::PassInOutNames`].
+//!
+//! # tokio Integration
+//!
+//! With the `tokio` feature enabled, [`AsyncReader`] reads passwords
+//! in [tokio](https://tokio.rs) applications without blocking the runtime.
+//! It reads the same sources as [`Reader`], whose builder methods set it up,
+//! and shares file-like sources the same way.
+//! Files and pipes that it opens or closes itself are read with async I/O,
+//! and standard input, prompts and other blocking sources on the blocking thread pool.
+//!
 //! # Passargs Sharing Same File-like Source
 //!
 //! As explained in [Passphrase Argument Syntax](#passphrase-argument-syntax) above,
@@ -100,34 +274,158 @@
 //! reads `--pass-in` first then `--pass-out`,
 //! implementing the same input-password-first ordering as with OpenSSL.
 //!
+//! To have [`Reader`] enforce the order instead,
+//! register the arguments as named slots in order with [`Reader::register_slot()`],
+//! then read them with [`Reader::read_slot()`] in any order:
+//! reading a slot first reads the earlier slots that share its file-like source,
+//! and keeps their passwords until they are asked for.
+//!
+//! Arguments that name the same open file also share its stream of lines,
+//! even when spelled differently:
+//! **stdin**, **fd:0** and **file:/dev/stdin** all read standard input,
+//! **file:/dev/fd/**_N_ and **file:/proc/self/fd/**_N_ are read as **fd:**_N_
+//! (but leave the file descriptor open, as the program still holds it),
+//! and **file:** paths and file descriptors are matched by device and inode numbers,
+//! so that for example `--pass-in stdin --pass-out fd:0` reads two lines from standard input.
+//!
+//! # Declaring Passphrase Arguments
+//!
+//! With the `derive` feature enabled, `#[derive(PassArgs)]` declares the read order
+//! next to the arguments themselves, so that it survives refactoring:
+//!
+//! ```rust
+//! # #[cfg(feature = "derive")]
+//! # fn main() -> Result<(), passarg::Error> {
+//! use clap::Parser;
+//! use passarg::PassArgs;
+//!
+//! #[derive(Parser, PassArgs)]
+//! struct Cli {
+//!     #[arg(long, value_name = "SPEC")]
+//!     #[passarg(order = 1, default = "env:MY_PASS_IN")]
+//!     pass_in: Option<String>,
+//!
+//!     #[arg(long, value_name = "SPEC")]
+//!     #[passarg(order = 2)]
+//!     pass_out: Option<String>,
+//! }
+//!
+//! # unsafe { std::env::set_var("MY_PASS_IN", "MyDecryptionPassphrase") };
+//! let cli = Cli::parse();
+//! let secrets = cli.read_all(&mut passarg::Reader::new())?;
+//! // secrets.pass_in: SecretString, secrets.pass_out: Option<SecretString>
+//! # assert_eq!(secrets.pass_in.expose(), "MyDecryptionPassphrase");
+//! # assert!(secrets.pass_out.is_none());
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "derive"))]
+//! # fn main() {}
+//! ```
+//!
+//! The derived [`PassArgs::read_all()`] reads the annotated fields in ascending `order`
+//! into the same-named fields of a generated `CliSecrets` struct.
+//! An `Option` field that is `None` reads its `default` source if any,
+//! and is otherwise left out as `None`.
+//! Fields may be of type [`Source`], `String` or `OsString`.
+//!
+//! # Secret Handling
+//!
+//! [`Reader::read_pass_arg_secret()`] and [`Reader::read_source_secret()`]
+//! return the password as a [`SecretString`],
+//! which wipes its contents from memory when dropped.
+//! [`Reader::read_pass_arg()`] and [`Reader::read_source()`]
+//! return a plain `String` instead, and are kept for compatibility.
+//!
+//! Passwords need not be valid UTF-8:
+//! [`Reader::read_pass_arg_bytes()`] and [`Reader::read_source_bytes()`]
+//! return the raw bytes as a [`SecretBytes`], which is wiped likewise,
+//! and [`Reader::read_source_os_string()`] returns an `OsString`.
+//! The `String` methods fail with [`Error::InvalidUtf8`] instead.
+//!
+//! The [`Display`] and [`Debug`](std::fmt::Debug) output
+//! of a [`Source`] redacts literal (**pass:**) passwords,
+//! so that sources can be logged safely.
+//!
+//! # Errors
+//!
+//! Every [`Error`] names the passphrase argument that failed,
+//! redacted the same way, as in
+//! `cannot read password from file:/x: permission denied`,
+//! so that users can tell which argument to fix.
+//! [`Error::spec()`] returns the failed [`Source`],
+//! and [`Error::kind()`] classifies the error as an [`ErrorKind`]
+//! for programs that need to react to specific failures.
+//!
 //! [openssl-passphrase-options(1)]: https://docs.openssl.org/3.3/man1/openssl-passphrase-options/
 //! [`rpassword::prompt_password()`]: https://docs.rs/rpassword/latest/rpassword/fn.prompt_password.html
+//! [`PassArgs::read_all()`]: https://docs.rs/passarg/latest/passarg/trait.PassArgs.html#tymethod.read_all
+//! [`clap`]: https://docs.rs/passarg/latest/passarg/clap/index.html
+//! [`clap::SourceValueParser`]: https://docs.rs/passarg/latest/passarg/clap/struct.SourceValueParser.html
+//! [`clap::PassInOut`]: https://docs.rs/passarg/latest/passarg/clap/struct.PassInOut.html
+//! [`AsyncReader`]: https://docs.rs/passarg/latest/passarg/struct.AsyncReader.html
+//! [`clap::PassInOutNames`]: https://docs.rs/passarg/latest/passarg/clap/trait.PassInOutNames.html
+
+#[cfg(feature = "tokio")]
+mod async_reader;
+#[cfg(feature = "clap")]
+pub mod clap;
+mod environ;
+mod error;
+mod exec;
+mod files;
+mod keyring;
+mod line;
+mod once;
+mod pass_args;
+mod perm;
+mod policy;
+mod provider;
+mod secret;
+mod tty;
+
+#[cfg(feature = "tokio")]
+pub use async_reader::AsyncReader;
+pub use error::{Error, ErrorKind};
+pub use line::LineEnding;
+pub use pass_args::PassArgs;
+#[cfg(feature = "derive")]
+pub use passarg_derive::PassArgs;
+pub use perm::PermissionCheck;
+pub use policy::{Policy, SourceKind};
+pub use provider::SourceProvider;
+pub use secret::{SecretBytes, SecretString};
 
 use rpassword::prompt_password;
 use std::collections::HashMap;
 use std::env;
+use std::ffi::{OsStr, OsString};
 use std::fmt::Display;
 use std::fs::File;
-use std::io::{stdin, BufRead, BufReader, StdinLock};
-use std::num::ParseIntError;
-use std::os::fd::{FromRawFd, RawFd};
+use std::io::Read;
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+use std::process::Command;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
 
-/// Errors that can arise while reading password argument.
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("invalid type {0}")]
-    InvalidType(String),
-    #[error("{0}")]
-    EnvVar(#[from] env::VarError),
-    #[error("{0}")]
-    Io(#[from] std::io::Error),
-    #[error("{0}")]
-    FdLiteral(#[from] ParseIntError),
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::pass_args::{read_defaulted, read_optional, read_required, SpecField};
 }
 
+use error::ResultExt;
+use files::{Files, Found, Target};
+use line::{open_without_waiting, wait_readable, FileId, LineOptions, LineReader, Stream};
+
 /// Password source.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// The [`Display`] and [`Debug`](std::fmt::Debug) output of a `Source`
+/// never includes a literal password;
+/// use [`Source::to_spec_string_unredacted()`] to obtain the full spec.
+#[derive(Clone, PartialEq)]
 pub enum Source {
     /// Literal password string.
     Pass(String),
@@ -135,68 +433,509 @@ pub enum Source {
     Env(std::ffi::OsString),
     /// File.
     File(std::path::PathBuf),
+    /// File, removed once read; see [`Reader::finish()`].
+    FileOnce(std::path::PathBuf),
     /// File descriptor.
     Fd(RawFd),
     /// Standard input.
     Stdin,
     /// User input.
     Prompt(String),
+    /// User input, entered twice for confirmation.
+    PromptNew(String),
+    /// Command, run without a shell, whose first argument is the program.
+    Exec(Vec<String>),
+    /// Shell command, run with `/bin/sh -c`.
+    Shell(String),
+    /// Key in a Linux kernel keyring.
+    Keyring {
+        /// Keyring to search, such as `user` or `@s`.
+        keyring: String,
+        /// Description (name) of the key.
+        description: String,
+    },
+    /// systemd credential.
+    Credential(String),
+    /// Binary keyfile, read whole.
+    Keyfile(std::path::PathBuf),
+    /// Source of a custom scheme, read by the [`SourceProvider`]
+    /// registered for `scheme` with [`Reader::with_scheme()`].
+    Custom {
+        /// Scheme, such as `vault`.
+        scheme: String,
+        /// The rest of the argument after `scheme:`, or empty if there is none.
+        rest: String,
+    },
+    /// Fallback chain; the first source that exists supplies the password.
+    Chain(Vec<Source>),
+}
+
+/// How much of a systemd credential (**cred:**) [`Reader`] reads.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialMode {
+    /// Only the first line, up to the newline character, like **file:**.
+    #[default]
+    FirstLine,
+    /// The entire content, as is.
+    Whole,
 }
 
 impl FromStr for Source {
     type Err = Error;
 
+    /// Parses a password argument of the built-in schemes;
+    /// use [`Reader::parse_pass_arg()`] to also accept custom schemes.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s.splitn(2, ':').collect::<Vec<_>>()[..] {
-            [] => panic!("splitn returned nothing"),
-            ["pass", password] => Self::Pass(password.into()),
-            ["env", var] => Self::Env(var.into()),
-            ["file", path] => Self::File(path.into()),
-            ["fd", fd] => Self::Fd(fd.parse()?),
-            ["stdin"] => Self::Stdin,
-            ["prompt"] => Self::Prompt("Password: ".to_string()),
-            ["prompt", prompt] => Self::Prompt(prompt.into()),
-            [t, ..] => return Err(Error::InvalidType(t.into())),
-        })
+        Self::parse_chain(s, Self::parse_link)
     }
 }
 
-impl Display for Source {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl TryFrom<&OsStr> for Source {
+    type Error = Error;
+
+    fn try_from(s: &OsStr) -> Result<Self, Self::Error> {
+        Self::from_os_str(s)
+    }
+}
+
+impl Source {
+    /// Parses a password argument that need not be valid UTF-8,
+    /// such as one from [`std::env::args_os()`].
+    ///
+    /// The variable name of **env:** and the *pathname* of **file:**, **file-once:** and **keyfile:**
+    /// are taken as is; the rest of the argument must be valid UTF-8.
+    pub fn from_os_str(s: &OsStr) -> Result<Self, Error> {
+        if let Some(s) = s.to_str() {
+            return s.parse();
+        }
+        let mut links = Vec::new();
+        let mut rest = s.as_bytes();
+        while let Some(i) = rest.windows(2).position(|w| w == b"||") {
+            links.push(Self::parse_os_link(&rest[..i])?);
+            rest = &rest[i + 2..];
+        }
+        if links.is_empty() {
+            return Self::parse_os_link(rest);
+        }
+        links.push(Self::parse_os_link(rest)?);
+        Ok(Self::Chain(links))
+    }
+
+    fn parse_os_link(s: &[u8]) -> Result<Self, Error> {
+        if let Ok(s) = std::str::from_utf8(s) {
+            return Self::parse_link(s);
+        }
+        let Some(i) = s.iter().position(|&b| b == b':') else {
+            let spec = String::from_utf8_lossy(s);
+            return Err(Error::invalid_spec(&spec, "not valid UTF-8"));
+        };
+        let rest = OsStr::from_bytes(&s[i + 1..]);
+        Ok(match &s[..i] {
+            b"env" => Self::Env(rest.into()),
+            b"file" => Self::File(rest.into()),
+            b"file-once" => Self::FileOnce(rest.into()),
+            b"keyfile" => Self::Keyfile(rest.into()),
+            t => {
+                let spec = format!("{}:<redacted>", String::from_utf8_lossy(t));
+                return Err(Error::invalid_spec(&spec, "not valid UTF-8"));
+            }
+        })
+    }
+
+    /// Parses a chain of links split on `||` with `parse_link`,
+    /// or a single link if `s` has no `||`.
+    fn parse_chain(
+        s: &str,
+        mut parse_link: impl FnMut(&str) -> Result<Self, Error>,
+    ) -> Result<Self, Error> {
+        if s.contains("||") {
+            return Ok(Self::Chain(
+                s.split("||").map(parse_link).collect::<Result<_, _>>()?,
+            ));
+        }
+        parse_link(s)
+    }
+
+    fn parse_link(s: &str) -> Result<Self, Error> {
+        let (scheme, rest) = split_scheme(s);
+        if !provider::BUILTIN_SCHEMES.contains(&scheme) {
+            return Err(unknown_scheme(scheme, rest));
+        }
+        Self::parse_builtin(scheme, rest)
+    }
+
+    /// Parses a link of a built-in scheme.
+    pub(crate) fn parse_builtin(scheme: &str, rest: Option<&str>) -> Result<Self, Error> {
+        let spec = match rest {
+            Some(rest) => format!("{scheme}:{rest}"),
+            None => scheme.to_string(),
+        };
+        let s = spec.as_str();
+        Ok(match (scheme, rest) {
+            ("pass", Some(password)) => Self::Pass(password.into()),
+            ("env", Some(var)) => Self::Env(var.into()),
+            ("file", Some(path)) => Self::File(path.into()),
+            ("file-once", Some(path)) => Self::FileOnce(path.into()),
+            ("fd", Some(fd)) => {
+                let fd = fd
+                    .parse()
+                    .map_err(|e| Error::invalid_spec(s, format!("invalid fd: {e}")))?;
+                check_fd_number(fd).map_err(|reason| Error::invalid_spec(s, reason))?;
+                Self::Fd(fd)
+            }
+            ("stdin", None) => Self::Stdin,
+            ("prompt", None) => Self::Prompt("Password: ".to_string()),
+            ("prompt", Some(prompt)) => Self::Prompt(prompt.into()),
+            ("prompt-new", None) => Self::PromptNew("New password: ".to_string()),
+            ("prompt-new", Some(prompt)) => Self::PromptNew(prompt.into()),
+            ("exec" | "cmd", Some(cmd)) => Self::Exec(
+                exec::split_command_line(cmd)
+                    .filter(|args| !args.is_empty())
+                    .ok_or_else(|| Error::invalid_spec(s, "invalid command line"))?,
+            ),
+            ("sh", Some(cmd)) => Self::Shell(cmd.into()),
+            ("keyring" | "keyctl", Some(spec)) => match spec.split_once('/') {
+                Some((keyring, description))
+                    if keyring::keyring_id(keyring).is_some() && !description.is_empty() =>
+                {
+                    Self::Keyring {
+                        keyring: keyring.into(),
+                        description: description.into(),
+                    }
+                }
+                _ => return Err(Error::invalid_spec(s, "invalid keyring or key description")),
+            },
+            ("cred", Some(name)) => {
+                if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+                    return Err(Error::invalid_spec(s, "invalid credential name"));
+                }
+                Self::Credential(name.into())
+            }
+            ("keyfile", Some(path)) => Self::Keyfile(path.into()),
+            (t, rest) => return Err(unknown_scheme(t, rest)),
+        })
+    }
+
+    /// Parses a [`Source::Custom`] of a built-in scheme.
+    /// An empty `rest` stands for none, as with [`SourceProvider::parse()`],
+    /// unless the scheme requires one, as **pass:** does.
+    fn parse_custom(scheme: &str, rest: &str) -> Result<Self, Error> {
+        if !rest.is_empty() {
+            return Self::parse_builtin(scheme, Some(rest));
+        }
+        Self::parse_builtin(scheme, None)
+            .or_else(|e| Self::parse_builtin(scheme, Some("")).map_err(|_| e))
+    }
+
+    /// Returns the scheme of this source, or `None` for a chain.
+    pub(crate) fn scheme(&self) -> Option<&str> {
+        use Source::*;
+        Some(match self {
+            Pass(_) => "pass",
+            Env(_) => "env",
+            File(_) => "file",
+            FileOnce(_) => "file-once",
+            Fd(_) => "fd",
+            Stdin => "stdin",
+            Prompt(_) => "prompt",
+            PromptNew(_) => "prompt-new",
+            Exec(_) => "exec",
+            Shell(_) => "sh",
+            Keyring { .. } => "keyring",
+            Credential(_) => "cred",
+            Keyfile(_) => "keyfile",
+            Custom { scheme, .. } => scheme,
+            Chain(_) => return None,
+        })
+    }
+
+    /// Returns the spec string of this source, including any literal password,
+    /// such that parsing it yields the same source.
+    ///
+    /// Environment variable names and paths that are not valid UTF-8
+    /// are converted lossily, and do not round-trip;
+    /// use [`Source::to_spec_os_string_unredacted()`] for those.
+    pub fn to_spec_string_unredacted(&self) -> String {
+        struct Unredacted<'a>(&'a Source);
+
+        impl Display for Unredacted<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.fmt_spec(f, false)
+            }
+        }
+
+        Unredacted(self).to_string()
+    }
+
+    /// Same as [`Source::to_spec_string_unredacted()`], but returns an `OsString`
+    /// that keeps environment variable names and paths as is,
+    /// such that [`Source::from_os_str()`] always yields the same source.
+    pub fn to_spec_os_string_unredacted(&self) -> OsString {
+        let (prefix, value) = match self {
+            Source::Env(var) => ("env:", var.as_os_str()),
+            Source::File(path) => ("file:", path.as_os_str()),
+            Source::FileOnce(path) => ("file-once:", path.as_os_str()),
+            Source::Keyfile(path) => ("keyfile:", path.as_os_str()),
+            Source::Chain(links) => {
+                let mut spec = OsString::new();
+                for (i, link) in links.iter().enumerate() {
+                    if i > 0 {
+                        spec.push("||");
+                    }
+                    spec.push(link.to_spec_os_string_unredacted());
+                }
+                return spec;
+            }
+            _ => return self.to_spec_string_unredacted().into(),
+        };
+        let mut spec = OsString::from(prefix);
+        spec.push(value);
+        spec
+    }
+
+    fn fmt_spec(&self, f: &mut std::fmt::Formatter<'_>, redact: bool) -> std::fmt::Result {
         use Source::*;
         match self {
+            Pass(_) if redact => write!(f, "pass:<redacted>"),
             Pass(password) => write!(f, "pass:{password}"),
-            Env(var) => {
-                let var = var.clone().into_string().map_err(|_| std::fmt::Error)?;
-                write!(f, "env:{var}")
-            }
-            File(path) => {
-                let path = path
-                    .clone()
-                    .into_os_string()
-                    .into_string()
-                    .map_err(|_| std::fmt::Error)?;
-                write!(f, "file:{path}")
-            }
+            Env(var) => write!(f, "env:{}", var.to_string_lossy()),
+            File(path) => write!(f, "file:{}", path.display()),
+            FileOnce(path) => write!(f, "file-once:{}", path.display()),
             Fd(fd) => write!(f, "fd:{fd}"),
             Stdin => write!(f, "stdin"),
             Prompt(prompt) => write!(f, "prompt:{prompt}"),
+            PromptNew(prompt) => write!(f, "prompt-new:{prompt}"),
+            Exec(args) => write!(f, "exec:{}", exec::join_command_line(args)),
+            Shell(cmd) => write!(f, "sh:{cmd}"),
+            Keyring {
+                keyring,
+                description,
+            } => write!(f, "keyring:{keyring}/{description}"),
+            Credential(name) => write!(f, "cred:{name}"),
+            Keyfile(path) => write!(f, "keyfile:{}", path.display()),
+            Custom { scheme, rest } => write!(f, "{scheme}:{rest}"),
+            Chain(links) => {
+                for (i, link) in links.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("||")?;
+                    }
+                    link.fmt_spec(f, redact)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_spec(f, true)
+    }
+}
+
+impl std::fmt::Debug for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Source::*;
+        match self {
+            Pass(_) => f
+                .debug_tuple("Pass")
+                .field(&format_args!("<redacted>"))
+                .finish(),
+            Env(var) => f.debug_tuple("Env").field(var).finish(),
+            File(path) => f.debug_tuple("File").field(path).finish(),
+            FileOnce(path) => f.debug_tuple("FileOnce").field(path).finish(),
+            Fd(fd) => f.debug_tuple("Fd").field(fd).finish(),
+            Stdin => f.write_str("Stdin"),
+            Prompt(prompt) => f.debug_tuple("Prompt").field(prompt).finish(),
+            PromptNew(prompt) => f.debug_tuple("PromptNew").field(prompt).finish(),
+            Exec(args) => f.debug_tuple("Exec").field(args).finish(),
+            Shell(cmd) => f.debug_tuple("Shell").field(cmd).finish(),
+            Keyring {
+                keyring,
+                description,
+            } => f
+                .debug_struct("Keyring")
+                .field("keyring", keyring)
+                .field("description", description)
+                .finish(),
+            Credential(name) => f.debug_tuple("Credential").field(name).finish(),
+            Keyfile(path) => f.debug_tuple("Keyfile").field(path).finish(),
+            Custom { scheme, rest } => f
+                .debug_struct("Custom")
+                .field("scheme", scheme)
+                .field("rest", rest)
+                .finish(),
+            Chain(links) => f.debug_tuple("Chain").field(links).finish(),
         }
     }
 }
 
+/// Splits a link into its scheme and the rest after the first `:`, if any.
+fn split_scheme(s: &str) -> (&str, Option<&str>) {
+    match s.split_once(':') {
+        Some((scheme, rest)) => (scheme, Some(rest)),
+        None => (s, None),
+    }
+}
+
+fn unknown_scheme(scheme: &str, rest: Option<&str>) -> Error {
+    // The rest may be a mistyped pass: password, so leave it out.
+    let spec = match rest {
+        Some(_) => format!("{scheme}:<redacted>"),
+        None => scheme.to_string(),
+    };
+    Error::invalid_spec(&spec, format!("unknown type {scheme:?}"))
+}
+
+/// Returns the file descriptor that `path` names,
+/// for `/dev/stdin`, `/dev/fd/N` and `/proc/self/fd/N`.
+fn fd_path(path: &Path) -> Option<RawFd> {
+    if path == Path::new("/dev/stdin") {
+        return Some(0);
+    }
+    let n = path
+        .strip_prefix("/dev/fd")
+        .or_else(|_| path.strip_prefix("/proc/self/fd"))
+        .ok()?;
+    n.to_str()?.parse().ok()
+}
+
+/// Rejects file descriptor numbers that cannot be password sources.
+fn check_fd_number(fd: RawFd) -> Result<(), &'static str> {
+    match fd {
+        i32::MIN..=-1 => Err("invalid fd: negative number"),
+        1 => Err("fd 1 is standard output"),
+        2 => Err("fd 2 is standard error"),
+        _ => Ok(()),
+    }
+}
+
+/// Named password read in registration order; see [`Reader::register_slot()`].
+struct Slot {
+    name: String,
+    source: Source,
+    state: SlotState,
+}
+
+enum SlotState {
+    Unread,
+    Read(Result<(SecretBytes, Source), Error>),
+    Taken,
+}
+
 /// Password argument reader.
 ///
 /// The main function, [Reader::read_pass_arg()], reads one password from the given source,
 /// opening the resources (such as files, file descriptors) as needed.
 ///
-/// When `Reader` goes out of scope, it closes all files and file descriptors opened it opened.
-/// `Reader` leaves stdin open even when used.
-#[derive(Default)]
+/// When `Reader` goes out of scope, it closes all files and file descriptors it opened,
+/// unless told to leave file descriptors open with [`Reader::with_close_fds()`].
+/// `Reader` leaves stdin (including **fd:0**) open even when used.
+///
+/// `Reader` does its own buffering of file-like sources:
+/// bytes are wiped from its buffers as soon as they are returned,
+/// and any read-ahead left over is wiped when `Reader` goes out of scope.
+/// Sources that name the same open file, such as **stdin** and **fd:0**,
+/// or two paths of the same file, share one stream of lines.
+/// Standard input is read unbuffered, so that `Reader` never consumes stdin
+/// past the lines it returns.
 pub struct Reader<'a> {
-    files: HashMap<std::path::PathBuf, BufReader<File>>,
-    fds: HashMap<RawFd, BufReader<File>>,
-    stdin: Option<StdinLock<'a>>,
+    files: Files<Stream<'a>>,
+    slots: Vec<Slot>,
+    settings: Settings,
+}
+
+/// Settings of a [`Reader`], set with its builder methods.
+#[derive(Clone)]
+struct Settings {
+    providers: HashMap<String, Arc<dyn SourceProvider>>,
+    policy: Policy,
+    permission_check: PermissionCheck,
+    permission_warning: Arc<dyn Fn(&Error) + Send + Sync>,
+    credential_mode: CredentialMode,
+    keyfile_limit: u64,
+    prompt_retries: u32,
+    line_options: LineOptions,
+    close_fds: bool,
+    timeout: Option<Duration>,
+    remove_env: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            providers: HashMap::new(),
+            policy: Policy::default(),
+            permission_check: PermissionCheck::default(),
+            permission_warning: Arc::new(|e| eprintln!("warning: {e}")),
+            credential_mode: CredentialMode::default(),
+            keyfile_limit: 8 << 20,
+            prompt_retries: 2,
+            line_options: LineOptions::default(),
+            close_fds: true,
+            timeout: None,
+            remove_env: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Turns an I/O error that timed out into [`Error::Timeout`].
+    fn timed_out(&self, e: Error) -> Error {
+        match self.timeout {
+            Some(timeout) => e.with_timeout(timeout),
+            None => e,
+        }
+    }
+
+    fn parse_pass_arg(&self, arg: &str) -> Result<Source, Error> {
+        Source::parse_chain(arg, |link| {
+            let (scheme, rest) = split_scheme(link);
+            match self.providers.get(scheme) {
+                Some(provider) => provider.parse(scheme, rest),
+                None => Source::parse_link(link),
+            }
+        })
+    }
+
+    /// Returns the provider registered for the scheme of `source`, if any.
+    fn provider(&self, source: &Source) -> Option<Arc<dyn SourceProvider>> {
+        source
+            .scheme()
+            .and_then(|scheme| self.providers.get(scheme))
+            .cloned()
+    }
+
+    /// Parses a built-in `source`, such as one from clap, with `provider`
+    /// as the argument it stands for.
+    fn reparse(&self, provider: &dyn SourceProvider, source: &Source) -> Result<Source, Error> {
+        let spec = Zeroizing::new(source.to_spec_string_unredacted());
+        let (scheme, rest) = split_scheme(&spec);
+        let source = provider.parse(scheme, rest)?;
+        self.policy.check(&source)?;
+        Ok(source)
+    }
+
+    /// Checks the permissions of `f`, opened from `path`,
+    /// as set by [`Reader::with_permission_check()`].
+    fn check_permissions(&self, path: &Path, f: &File, source: &Source) -> Result<(), Error> {
+        match self.permission_check {
+            PermissionCheck::Off => Ok(()),
+            PermissionCheck::Warn => {
+                if let Err(e) = perm::check_file(path, f, source) {
+                    (self.permission_warning)(&e);
+                }
+                Ok(())
+            }
+            PermissionCheck::Strict => perm::check_file(path, f, source),
+        }
+    }
+}
+
+impl Default for Reader<'_> {
+    fn default() -> Self {
+        Self::with_settings(Settings::default())
+    }
 }
 
 impl Reader<'_> {
@@ -204,55 +943,572 @@ impl Reader<'_> {
         Self::default()
     }
 
+    fn with_settings(settings: Settings) -> Self {
+        Self {
+            files: Files::default(),
+            slots: Vec::new(),
+            settings,
+        }
+    }
+
+    /// Sets whether reading past the last line of a file-like source
+    /// yields an empty password, as it did in passarg 0.2,
+    /// instead of failing with [`Error::UnexpectedEof`].
+    /// An empty line (a lone newline character) is always an empty password.
+    /// The default is `false`.
+    pub fn with_eof_as_empty(mut self, eof_as_empty: bool) -> Self {
+        self.settings.line_options.eof_as_empty = eof_as_empty;
+        self
+    }
+
+    /// Sets the line endings recognized in file-like sources.
+    /// The default is [`LineEnding::Lf`], as with OpenSSL.
+    pub fn with_line_ending(mut self, ending: LineEnding) -> Self {
+        self.settings.line_options.ending = ending;
+        self
+    }
+
+    /// Sets whether a UTF-8 byte order mark (BOM)
+    /// at the start of a file-like source is removed.
+    /// The default is `false`, as with OpenSSL.
+    pub fn with_strip_bom(mut self, strip_bom: bool) -> Self {
+        self.settings.line_options.strip_bom = strip_bom;
+        self
+    }
+
+    /// Sets how many more times a confirming prompt (**prompt-new:**)
+    /// asks again after the two entries do not match,
+    /// before failing with [`Error::Mismatch`].
+    /// The default is 2.
+    pub fn with_prompt_retries(mut self, retries: u32) -> Self {
+        self.settings.prompt_retries = retries;
+        self
+    }
+
+    /// Sets how much of a systemd credential (**cred:**) to read.
+    /// The default is [`CredentialMode::FirstLine`].
+    pub fn with_credential_mode(mut self, mode: CredentialMode) -> Self {
+        self.settings.credential_mode = mode;
+        self
+    }
+
+    /// Sets the largest keyfile (**keyfile:**) in bytes
+    /// that can be read before failing with [`Error::TooLarge`].
+    /// The default is 8 MiB, as with `cryptsetup(8)`.
+    pub fn with_keyfile_limit(mut self, limit: u64) -> Self {
+        self.settings.keyfile_limit = limit;
+        self
+    }
+
+    /// Sets whether file descriptors read by **fd:** sources
+    /// are closed when `Reader` goes out of scope.
+    /// Set it to `false` if the program owns those file descriptors
+    /// and keeps using them;
+    /// they are then read unbuffered like standard input,
+    /// so that `Reader` never consumes them past the lines it returns.
+    /// Standard input (**fd:0**) is never closed.
+    /// The default is `true`.
+    pub fn with_close_fds(mut self, close_fds: bool) -> Self {
+        self.settings.close_fds = close_fds;
+        self
+    }
+
+    /// Sets how long a read from a file-like source (**file:**, **fd:** and **stdin**),
+    /// or each entry of a prompt (**prompt:** and **prompt-new:**), may take
+    /// before failing with [`Error::Timeout`],
+    /// e.g. when the writer of a pipe never writes.
+    ///
+    /// With a timeout, a FIFO named by **file:** is opened without waiting for a writer,
+    /// and prompts read the terminal directly instead of using [`rpassword`].
+    /// By default, reads wait forever.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether the variable of an **env:** source is removed from the environment
+    /// once its password is read,
+    /// so that child processes do not inherit it.
+    /// The value is not wiped from memory,
+    /// and may still show in `/proc/self/environ`.
+    /// The default is `false`.
+    ///
+    /// Like [`std::env::remove_var()`], this is not thread-safe:
+    /// other threads must not read or change the environment meanwhile,
+    /// including through C libraries that call `getenv(3)`,
+    /// so turn it on only while the program is single-threaded,
+    /// such as when reading arguments at the start of `main()`.
+    pub fn with_remove_env(mut self, remove_env: bool) -> Self {
+        self.settings.remove_env = remove_env;
+        self
+    }
+
+    /// Sets the policy restricting the sources read.
+    /// The default allows every source.
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.settings.policy = policy;
+        self
+    }
+
+    /// Sets how the permissions of **file:**, **file-once:** and **keyfile:** sources are checked.
+    /// The default is [`PermissionCheck::Off`].
+    pub fn with_permission_check(mut self, check: PermissionCheck) -> Self {
+        self.settings.permission_check = check;
+        self
+    }
+
+    /// Sets the callback that [`PermissionCheck::Warn`] reports insecure files to,
+    /// with the [`Error::InsecureFile`] that [`PermissionCheck::Strict`] would fail with.
+    /// The default prints the error to standard error.
+    pub fn with_permission_warning(
+        mut self,
+        warning: impl Fn(&Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.settings.permission_warning = Arc::new(warning);
+        self
+    }
+
+    /// Registers `provider` for the custom scheme `scheme`,
+    /// so that arguments such as `scheme:rest` can be read;
+    /// see [`SourceProvider`].
+    /// This replaces any provider already registered for `scheme`,
+    /// and a built-in scheme of that name, also for [`Source`] values read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scheme` is empty, or contains `:` or `|`.
+    pub fn with_scheme(
+        mut self,
+        scheme: impl Into<String>,
+        provider: impl SourceProvider + 'static,
+    ) -> Self {
+        let scheme = scheme.into();
+        assert!(
+            !scheme.is_empty() && !scheme.contains([':', '|']),
+            "invalid scheme {scheme:?}"
+        );
+        self.settings.providers.insert(scheme, Arc::new(provider));
+        self
+    }
+
+    /// Parses a password argument like [`Source::from_str()`],
+    /// with the schemes registered with [`Reader::with_scheme()`].
+    pub fn parse_pass_arg(&self, arg: &str) -> Result<Source, Error> {
+        self.settings.parse_pass_arg(arg)
+    }
+
+    /// Registers the slot `name`, whose password [`Reader::read_slot()`] reads from `source`.
+    ///
+    /// Slots sharing a file-like source are read in the order they are registered,
+    /// whatever order [`Reader::read_slot()`] is called in:
+    /// reading a slot first reads the earlier unread slots that share its source,
+    /// and keeps their passwords until they are asked for.
+    /// Fails with [`Error::DuplicateSlot`] if `name` is already registered,
+    /// or with [`Error::PolicyViolation`] if the policy forbids `source`.
+    pub fn register_slot(&mut self, name: impl Into<String>, source: Source) -> Result<(), Error> {
+        self.settings.policy.check(&source)?;
+        let name = name.into();
+        if self.slots.iter().any(|slot| slot.name == name) {
+            return Err(Error::DuplicateSlot { name });
+        }
+        self.slots.push(Slot {
+            name,
+            source,
+            state: SlotState::Unread,
+        });
+        Ok(())
+    }
+
+    /// Reads the password of the slot `name`; see [`Reader::register_slot()`].
+    ///
+    /// Fails with [`Error::UnknownSlot`] if no such slot is registered,
+    /// or with [`Error::SlotAlreadyRead`] if it has already been read.
+    pub fn read_slot(&mut self, name: &str) -> Result<SecretString, Error> {
+        let (bytes, origin) = self.take_slot(name)?;
+        bytes
+            .into_secret_string()
+            .ok_or(Error::InvalidUtf8 { spec: origin })
+    }
+
+    /// Same as [`Reader::read_slot()`], but returns raw bytes
+    /// like [`Reader::read_source_bytes()`].
+    pub fn read_slot_bytes(&mut self, name: &str) -> Result<SecretBytes, Error> {
+        self.take_slot(name).map(|(bytes, _)| bytes)
+    }
+
+    fn take_slot(&mut self, name: &str) -> Result<(SecretBytes, Source), Error> {
+        let index = self
+            .slots
+            .iter()
+            .position(|slot| slot.name == name)
+            .ok_or_else(|| Error::UnknownSlot { name: name.into() })?;
+        if let SlotState::Unread = self.slots[index].state {
+            let source = self.slots[index].source.clone();
+            let streams = self.slot_streams(&source);
+            // A slot without a file-like source shares nothing with earlier slots.
+            let shared = if streams.is_empty() { 0 } else { index };
+            for earlier in 0..shared {
+                if !matches!(self.slots[earlier].state, SlotState::Unread) {
+                    continue;
+                }
+                let earlier_source = self.slots[earlier].source.clone();
+                if self
+                    .slot_streams(&earlier_source)
+                    .iter()
+                    .any(|id| streams.contains(id))
+                {
+                    let result = self.read_source_bytes_with_origin(earlier_source);
+                    self.slots[earlier].state = SlotState::Read(result);
+                }
+            }
+            self.slots[index].state = SlotState::Read(self.read_source_bytes_with_origin(source));
+        }
+        match std::mem::replace(&mut self.slots[index].state, SlotState::Taken) {
+            SlotState::Read(result) => result,
+            _ => Err(Error::SlotAlreadyRead { name: name.into() }),
+        }
+    }
+
+    /// Returns the streams that reading `source` may read from.
+    fn slot_streams(&self, source: &Source) -> Vec<FileId> {
+        match source {
+            Source::Chain(links) => links
+                .iter()
+                .flat_map(|link| self.slot_streams(link))
+                .collect(),
+            // Errors are reported when the source itself is read.
+            _ => self.files.stream_id(source).into_iter().collect(),
+        }
+    }
+
     /// Reads and returns a password from the given source (`arg`).
     /// See package documentation for the accepted formats of `arg`.
+    ///
+    /// The returned `String` is not wiped from memory when dropped;
+    /// prefer [`Reader::read_pass_arg_secret()`].
     pub fn read_pass_arg(&mut self, arg: &str) -> Result<String, Error> {
-        self.read_source(arg.parse()?)
+        self.read_pass_arg_secret(arg)
+            .map(SecretString::into_unprotected)
     }
 
+    /// Same as [`Reader::read_pass_arg()`], but returns a [`SecretString`].
+    pub fn read_pass_arg_secret(&mut self, arg: &str) -> Result<SecretString, Error> {
+        self.read_source_secret(self.parse_pass_arg(arg)?)
+    }
+
+    /// Reads and returns a password from the given source.
+    ///
+    /// The returned `String` is not wiped from memory when dropped;
+    /// prefer [`Reader::read_source_secret()`].
     pub fn read_source(&mut self, source: Source) -> Result<String, Error> {
+        self.read_source_secret(source)
+            .map(SecretString::into_unprotected)
+    }
+
+    /// Same as [`Reader::read_source()`], but returns a [`SecretString`].
+    pub fn read_source_secret(&mut self, source: Source) -> Result<SecretString, Error> {
+        self.read_source_with_origin(source)
+            .map(|(secret, _)| secret)
+    }
+
+    /// Same as [`Reader::read_source_secret()`],
+    /// but also returns the source that supplied the password,
+    /// which is the first existing link if `source` is a [`Source::Chain`].
+    pub fn read_source_with_origin(
+        &mut self,
+        source: Source,
+    ) -> Result<(SecretString, Source), Error> {
+        let (bytes, origin) = self.read_source_bytes_with_origin(source)?;
+        match bytes.into_secret_string() {
+            Some(secret) => Ok((secret, origin)),
+            None => Err(Error::InvalidUtf8 { spec: origin }),
+        }
+    }
+
+    /// Same as [`Reader::read_pass_arg_secret()`],
+    /// but returns the password as raw bytes,
+    /// which need not be valid UTF-8.
+    pub fn read_pass_arg_bytes(&mut self, arg: &str) -> Result<SecretBytes, Error> {
+        self.read_source_bytes(self.parse_pass_arg(arg)?)
+    }
+
+    /// Same as [`Reader::read_source_secret()`],
+    /// but returns the password as raw bytes,
+    /// which need not be valid UTF-8.
+    ///
+    /// Use this to read binary keyfiles (**keyfile:**),
+    /// or passwords in a legacy encoding.
+    pub fn read_source_bytes(&mut self, source: Source) -> Result<SecretBytes, Error> {
+        self.read_source_bytes_with_origin(source)
+            .map(|(secret, _)| secret)
+    }
+
+    /// Same as [`Reader::read_source_bytes()`], but returns an `OsString`,
+    /// for example to pass the password on to another program.
+    ///
+    /// The returned `OsString` is not wiped from memory when dropped.
+    pub fn read_source_os_string(&mut self, source: Source) -> Result<OsString, Error> {
+        self.read_source_bytes(source)
+            .map(|secret| OsString::from_vec(secret.into_unprotected()))
+    }
+
+    /// Same as [`Reader::read_source_bytes()`],
+    /// but also returns the source that supplied the password,
+    /// like [`Reader::read_source_with_origin()`].
+    pub fn read_source_bytes_with_origin(
+        &mut self,
+        source: Source,
+    ) -> Result<(SecretBytes, Source), Error> {
+        self.settings.policy.check(&source)?;
+        let Source::Chain(links) = &source else {
+            return Ok((self.read_link(&source)?, source));
+        };
+        let mut errors = Vec::new();
+        for link in links {
+            match self.read_source_bytes_with_origin(link.clone()) {
+                Err(e) if e.is_not_found() => errors.push(e),
+                result => return result,
+            }
+        }
+        Err(Error::Chain {
+            spec: source,
+            errors,
+        })
+    }
+
+    /// Finishes reading, retrying to remove the files of **file-once:** sources
+    /// that could not be removed once read.
+    ///
+    /// Fails with [`Error::Unlink`] for the first file that still cannot be removed;
+    /// the passwords read from it are not affected.
+    /// Dropping the reader retries as well, but ignores failures.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.files.finish()
+    }
+
+    fn read_link(&mut self, source: &Source) -> Result<SecretBytes, Error> {
+        let Some(provider) = self.settings.provider(source) else {
+            return self.read_builtin(source);
+        };
+        match source {
+            Source::Custom { .. } => provider.read(source, self),
+            _ => {
+                let source = self.settings.reparse(&*provider, source)?;
+                provider.read(&source, self)
+            }
+        }
+    }
+
+    pub(crate) fn read_builtin(&mut self, source: &Source) -> Result<SecretBytes, Error> {
+        let options = self.settings.line_options;
         Ok(match source {
-            Source::Pass(password) => password,
-            Source::Env(var) => env::var(var)?,
-            Source::File(path) => {
-                let path = std::fs::canonicalize(path)?;
-                let f = match self.files.get_mut(&path) {
-                    Some(f) => f,
-                    None => {
-                        self.files
-                            .insert(path.clone(), BufReader::new(File::open(&path)?));
-                        self.files.get_mut(&path).unwrap()
-                    }
-                };
-                Self::read_from_bufreader(f)?
-            }
-            Source::Fd(fd) => {
-                let f = match self.fds.get_mut(&fd) {
-                    Some(f) => f,
-                    None => {
-                        self.fds
-                            .insert(fd, BufReader::new(unsafe { File::from_raw_fd(fd) }));
-                        self.fds.get_mut(&fd).unwrap()
-                    }
+            Source::Pass(password) => password.clone().into_bytes().into(),
+            Source::Env(var) => {
+                let value = env::var_os(var)
+                    .ok_or(env::VarError::NotPresent)
+                    .with_spec(source)?
+                    .into_vec();
+                if self.settings.remove_env {
+                    environ::remove(var);
+                }
+                value.into()
+            }
+            Source::File(_) | Source::FileOnce(_) | Source::Fd(_) | Source::Stdin => {
+                let id = self.open_stream(source)?;
+                let password = self.read_stream_line(id, source)?;
+                self.files.remove_once(source, id);
+                password
+            }
+            Source::Prompt(prompt) => (self.prompter())(prompt.clone())
+                .with_spec(source)
+                .map_err(|e| self.settings.timed_out(e))?
+                .into_bytes()
+                .into(),
+            Source::PromptNew(prompt) => Self::prompt_confirmed(
+                prompt,
+                self.settings.prompt_retries,
+                source,
+                self.prompter(),
+            )
+            .map_err(|e| self.settings.timed_out(e))?
+            .into(),
+            Source::Exec(args) => {
+                let Some((program, args)) = args.split_first() else {
+                    return Err(Error::invalid_spec("exec:", "empty command line"));
                 };
-                Self::read_from_bufreader(f)?
+                let mut cmd = Command::new(program);
+                cmd.args(args);
+                let line = exec::read_first_line(cmd, &options, source)?;
+                Self::line_to_secret(line, source, options)?
             }
-            Source::Stdin => {
-                Self::read_from_bufreader(self.stdin.get_or_insert_with(|| stdin().lock()))?
+            Source::Shell(cmd) => {
+                let mut sh = Command::new("/bin/sh");
+                sh.arg("-c").arg(cmd);
+                let line = exec::read_first_line(sh, &options, source)?;
+                Self::line_to_secret(line, source, options)?
+            }
+            Source::Keyring {
+                keyring,
+                description,
+            } => {
+                let keyring = keyring::keyring_id(keyring)
+                    .ok_or_else(|| Error::invalid_spec(&source.to_string(), "invalid keyring"))?;
+                let mut payload = keyring::read_key(keyring, description, source)?;
+                if payload.last() == Some(&b'\n') {
+                    payload.pop();
+                }
+                Self::into_secret(payload)
+            }
+            Source::Credential(name) => self.read_credential(name, source)?,
+            Source::Keyfile(path) => self.read_keyfile(path, source)?,
+            Source::Chain(_) => self.read_source_bytes(source.clone())?,
+            // A custom source of a built-in scheme, made by hand.
+            Source::Custom { scheme, rest } => {
+                let source = Source::parse_custom(scheme, rest)?;
+                self.settings.policy.check(&source)?;
+                self.read_builtin(&source)?
+            }
+        })
+    }
+
+    fn read_stream_line(&mut self, id: FileId, source: &Source) -> Result<SecretBytes, Error> {
+        let options = self.settings.line_options;
+        let r = self.files.stream(id);
+        let Some(timeout) = self.settings.timeout else {
+            return Self::read_line(r, source, options);
+        };
+        let deadline = Instant::now() + timeout;
+        let mut line = Zeroizing::new(Vec::new());
+        r.read_line_with(&mut line, &options, |stream| {
+            wait_readable(stream.as_raw_fd(), deadline)
+        })
+        .with_spec(source)
+        .map_err(|e| e.with_timeout(timeout))?;
+        Self::line_to_secret(line, source, options)
+    }
+
+    /// Opens the stream of a file-like source if not yet open, and returns its ID.
+    fn open_stream(&mut self, source: &Source) -> Result<FileId, Error> {
+        let path = match Target::of(source, self.settings.close_fds) {
+            Some(Target::Path(path)) => path,
+            Some(Target::Fd(fd, owned)) => return self.files.open_fd(fd, owned, source),
+            None => unreachable!("{source} is not file-like"),
+        };
+        let canonical = match self.files.find_file(path, &self.settings, source)? {
+            Found::Open(id) => return Ok(id),
+            Found::Closed(canonical) => canonical,
+        };
+        let f = match self.settings.timeout {
+            Some(_) => open_without_waiting(&canonical),
+            None => File::open(&canonical),
+        }
+        .with_spec(source)?;
+        self.files
+            .insert_file(path, canonical, f, &self.settings, source)
+    }
+
+    /// Returns the function that prompts for a password, honoring the timeout.
+    fn prompter(&self) -> impl FnMut(String) -> std::io::Result<String> {
+        let timeout = self.settings.timeout;
+        move |prompt| match timeout {
+            Some(timeout) => tty::prompt_password(&prompt, timeout),
+            None => prompt_password(prompt),
+        }
+    }
+
+    fn prompt_confirmed(
+        prompt: &str,
+        retries: u32,
+        source: &Source,
+        mut prompt_password: impl FnMut(String) -> std::io::Result<String>,
+    ) -> Result<SecretString, Error> {
+        let mut attempt = prompt.to_string();
+        for _ in 0..=retries {
+            let password = SecretString::from(prompt_password(attempt).with_spec(source)?);
+            let confirmation = SecretString::from(
+                prompt_password(format!("Verifying - {prompt}")).with_spec(source)?,
+            );
+            if password.expose() == confirmation.expose() {
+                return Ok(password);
             }
-            Source::Prompt(prompt) => prompt_password(prompt)?,
+            attempt = format!("Passwords do not match; try again.\n{prompt}");
+        }
+        Err(Error::Mismatch {
+            spec: source.clone(),
         })
     }
 
-    fn read_from_bufreader(r: &mut dyn BufRead) -> Result<String, Error> {
-        let mut line = String::new();
-        r.read_line(&mut line)?;
-        Ok(line.trim_end_matches('\n').into())
+    fn read_credential(&self, name: &str, source: &Source) -> Result<SecretBytes, Error> {
+        let dir = env::var_os("CREDENTIALS_DIRECTORY")
+            .filter(|dir| !dir.is_empty())
+            .ok_or_else(|| Error::NoCredentialsDirectory {
+                spec: source.clone(),
+            })?;
+        let path = std::path::Path::new(&dir).join(name);
+        let mut f = LineReader::new(File::open(path).with_spec(source)?);
+        match self.settings.credential_mode {
+            CredentialMode::FirstLine => {
+                Self::read_line(&mut f, source, self.settings.line_options)
+            }
+            CredentialMode::Whole => {
+                let mut content = Zeroizing::new(Vec::new());
+                f.read_to_end(&mut content).with_spec(source)?;
+                Ok(Self::into_secret(content))
+            }
+        }
+    }
+
+    fn read_keyfile(&self, path: &Path, source: &Source) -> Result<SecretBytes, Error> {
+        let canonical = std::fs::canonicalize(path).with_spec(source)?;
+        self.settings.policy.check_file_dir(&canonical, source)?;
+        let f = File::open(canonical).with_spec(source)?;
+        self.settings.check_permissions(path, &f, source)?;
+        let mut content = Zeroizing::new(Vec::new());
+        LineReader::new(f.take(self.settings.keyfile_limit.saturating_add(1)))
+            .read_to_end(&mut content)
+            .with_spec(source)?;
+        if content.len() as u64 > self.settings.keyfile_limit {
+            return Err(Error::TooLarge {
+                spec: source.clone(),
+                limit: self.settings.keyfile_limit,
+            });
+        }
+        Ok(Self::into_secret(content))
+    }
+
+    fn read_line<R: Read>(
+        r: &mut LineReader<R>,
+        source: &Source,
+        options: LineOptions,
+    ) -> Result<SecretBytes, Error> {
+        let mut line = Zeroizing::new(Vec::new());
+        r.read_line(&mut line, &options).with_spec(source)?;
+        Self::line_to_secret(line, source, options)
+    }
+
+    fn line_to_secret(
+        mut line: Zeroizing<Vec<u8>>,
+        source: &Source,
+        options: LineOptions,
+    ) -> Result<SecretBytes, Error> {
+        if line.is_empty() && !options.eof_as_empty {
+            return Err(Error::UnexpectedEof {
+                spec: source.clone(),
+            });
+        }
+        options.trim_ending(&mut line);
+        Ok(Self::into_secret(line))
+    }
+
+    fn into_secret(mut bytes: Zeroizing<Vec<u8>>) -> SecretBytes {
+        std::mem::take(&mut *bytes).into()
     }
 }
 
 #[cfg(test)]
 mod test {
+    // Shadows the `clap` module, which clap derive output would otherwise refer to.
+    use ::clap;
     use assert_ok::assert_ok;
     use clap::Parser as ClapParser;
 
@@ -277,5 +1533,778 @@ mod test {
         assert_eq!(exercise_clap("stdin"), Source::Stdin);
         assert_eq!(exercise_clap("prompt:omg"), Source::Prompt("omg".into()));
         assert_eq!(exercise_clap("prompt"), Source::Prompt("Password: ".into()));
+        assert_eq!(
+            exercise_clap("prompt-new"),
+            Source::PromptNew("New password: ".into())
+        );
+        assert_eq!(
+            exercise_clap("exec:pass show db"),
+            Source::Exec(vec!["pass".into(), "show".into(), "db".into()])
+        );
+        assert_eq!(exercise_clap("cmd:pass"), Source::Exec(vec!["pass".into()]));
+        assert_eq!(
+            exercise_clap("sh:pass show db"),
+            Source::Shell("pass show db".into())
+        );
+        assert_eq!(
+            exercise_clap("keyring:@u/my:db"),
+            Source::Keyring {
+                keyring: "@u".into(),
+                description: "my:db".into()
+            }
+        );
+    }
+
+    fn temp_file(name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("passarg-test-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Creates a pipe holding `contents`, returning its read and write ends.
+    pub(crate) fn pipe_with(contents: &[u8]) -> (RawFd, RawFd) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let [read_fd, write_fd] = fds;
+        let written = unsafe { libc::write(write_fd, contents.as_ptr().cast(), contents.len()) };
+        assert_eq!(written, contents.len() as isize);
+        (read_fd, write_fd)
+    }
+
+    #[test]
+    fn test_shared_file() {
+        let path = temp_file("shared", b"first\nsecond\n");
+        let mut r = Reader::new();
+        assert_eq!(
+            assert_ok!(r.read_source(Source::File(path.clone()))),
+            "first"
+        );
+        assert_eq!(
+            assert_ok!(r.read_source(Source::File(path.clone()))),
+            "second"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_source_secret() {
+        let mut r = Reader::new();
+        let secret = assert_ok!(r.read_source_secret(Source::Pass("omg".into())));
+        assert_eq!(secret.expose(), "omg");
+        assert_eq!(assert_ok!(r.read_pass_arg("pass:omg")), "omg");
+    }
+
+    #[test]
+    fn test_source_redaction() {
+        let source = Source::Pass("hunter2".into());
+        for formatted in [
+            source.to_string(),
+            format!("{source}"),
+            format!("{source:>40}"),
+            format!("{source:?}"),
+            format!("{source:#?}"),
+            format!("{:?}", Some(&source)),
+            format!("{:?}", vec![source.clone()]),
+        ] {
+            assert!(!formatted.contains("hunter2"), "{formatted}");
+        }
+        assert_eq!(source.to_string(), "pass:<redacted>");
+        assert_eq!(format!("{source:?}"), "Pass(<redacted>)");
+    }
+
+    #[test]
+    fn test_spec_string_round_trip() {
+        for spec in [
+            "pass:hunter2",
+            "env:omg",
+            "file:omg",
+            "fd:3",
+            "stdin",
+            "prompt:omg",
+            "exec:pass show 'my db'",
+            "sh:pass show db | head -1",
+            "keyring:user/my:db/pass",
+            "cred:db-pass",
+            "keyfile:/etc/luks.key",
+            "prompt-new:omg",
+            "env:APP_PASS||file:/run/secrets/app||prompt:omg",
+        ] {
+            let source: Source = assert_ok!(spec.parse());
+            assert_eq!(source.to_spec_string_unredacted(), spec);
+        }
+    }
+
+    #[test]
+    fn test_exec() {
+        let mut r = Reader::new();
+        assert_eq!(
+            assert_ok!(r.read_pass_arg("exec:printf 'hunter2\\nextra\\n'")),
+            "hunter2"
+        );
+        assert_eq!(
+            assert_ok!(r.read_pass_arg("sh:echo hunter2; echo extra")),
+            "hunter2"
+        );
+        match r.read_pass_arg("sh:echo hunter2; echo oops >&2; exit 3") {
+            Err(Error::Command { status, stderr, .. }) => {
+                assert_eq!(status.code(), Some(3));
+                assert_eq!(stderr, "oops");
+            }
+            result => panic!("unexpected {result:?}"),
+        }
+        assert!(matches!(
+            "exec:'unterminated".parse::<Source>(),
+            Err(Error::InvalidSpec { .. })
+        ));
+        assert!(matches!(
+            "exec:".parse::<Source>(),
+            Err(Error::InvalidSpec { .. })
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_keyring() {
+        for spec in ["keyring:omg/key", "keyring:user", "keyring:user/"] {
+            assert!(matches!(
+                spec.parse::<Source>(),
+                Err(Error::InvalidSpec { .. })
+            ));
+        }
+        if !keyring::test::add_process_key("passarg-test-keyring", b"hunter2\n") {
+            eprintln!("skipping keyring reads: keyrings are unavailable");
+            return;
+        }
+        let mut r = Reader::new();
+        assert_eq!(
+            assert_ok!(r.read_pass_arg("keyring:process/passarg-test-keyring")),
+            "hunter2"
+        );
+        assert!(matches!(
+            r.read_pass_arg("keyctl:@p/passarg-test-no-such-key"),
+            Err(Error::KeyNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_credential() {
+        env::remove_var("CREDENTIALS_DIRECTORY");
+        assert!(matches!(
+            Reader::new().read_pass_arg("cred:db-pass"),
+            Err(Error::NoCredentialsDirectory { .. })
+        ));
+        let path = temp_file("cred-db-pass", b"hunter2\nextra\n");
+        env::set_var("CREDENTIALS_DIRECTORY", path.parent().unwrap());
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let spec = format!("cred:{name}");
+        assert_eq!(assert_ok!(Reader::new().read_pass_arg(&spec)), "hunter2");
+        assert_eq!(assert_ok!(Reader::new().read_pass_arg(&spec)), "hunter2");
+        let mut r = Reader::new().with_credential_mode(CredentialMode::Whole);
+        assert_eq!(assert_ok!(r.read_pass_arg(&spec)), "hunter2\nextra\n");
+        env::remove_var("CREDENTIALS_DIRECTORY");
+        std::fs::remove_file(path).unwrap();
+        for spec in ["cred:", "cred:..", "cred:a/b"] {
+            assert!(matches!(
+                spec.parse::<Source>(),
+                Err(Error::InvalidSpec { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        let source: Source = assert_ok!("env:PASSARG_TEST_UNSET||pass:hunter2||prompt".parse());
+        assert_eq!(
+            source,
+            Source::Chain(vec![
+                Source::Env("PASSARG_TEST_UNSET".into()),
+                Source::Pass("hunter2".into()),
+                Source::Prompt("Password: ".into()),
+            ])
+        );
+        assert_eq!(
+            source.to_string(),
+            "env:PASSARG_TEST_UNSET||pass:<redacted>||prompt:Password: "
+        );
+        let mut r = Reader::new();
+        let (secret, origin) = assert_ok!(r.read_source_with_origin(source));
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(origin, Source::Pass("hunter2".into()));
+
+        let dir = env::temp_dir();
+        let spec = format!("file:{}||pass:hunter2", dir.display());
+        let e = r.read_pass_arg(&spec).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Other);
+        assert_eq!(e.spec(), Some(&Source::File(dir)));
+        let spec = "env:PASSARG_TEST_UNSET||file:/nonexistent/passarg";
+        match r.read_pass_arg(spec) {
+            Err(e @ Error::Chain { .. }) => {
+                assert!(e.is_not_found());
+                assert_eq!(e.spec(), Some(&assert_ok!(spec.parse())));
+            }
+            result => panic!("unexpected {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_prompt_confirmed() {
+        let source = Source::PromptNew("New: ".into());
+        let answers = |answers: &'static [&'static str]| {
+            let mut answers = answers.iter();
+            move |_| Ok(answers.next().unwrap().to_string())
+        };
+        let secret = assert_ok!(Reader::prompt_confirmed(
+            "New: ",
+            0,
+            &source,
+            answers(&["hunter2", "hunter2"])
+        ));
+        assert_eq!(secret.expose(), "hunter2");
+        let secret = assert_ok!(Reader::prompt_confirmed(
+            "New: ",
+            1,
+            &source,
+            answers(&["hunter2", "hunter3", "hunter4", "hunter4"])
+        ));
+        assert_eq!(secret.expose(), "hunter4");
+        assert!(matches!(
+            Reader::prompt_confirmed("New: ", 1, &source, answers(&["a", "b", "c", "d"])),
+            Err(Error::Mismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        let path = temp_file("eof", b"first\n\n");
+        let source = Source::File(path.clone());
+        let mut r = Reader::new();
+        assert_eq!(assert_ok!(r.read_source(source.clone())), "first");
+        assert_eq!(assert_ok!(r.read_source(source.clone())), "");
+        match r.read_source(source.clone()) {
+            Err(Error::UnexpectedEof { spec }) => assert_eq!(spec, source),
+            result => panic!("unexpected {result:?}"),
+        }
+        let mut r = Reader::new().with_eof_as_empty(true);
+        assert_eq!(assert_ok!(r.read_source(source.clone())), "first");
+        assert_eq!(assert_ok!(r.read_source(source.clone())), "");
+        assert_eq!(assert_ok!(r.read_source(source.clone())), "");
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(
+            Reader::new().read_pass_arg("sh:true"),
+            Err(Error::UnexpectedEof { .. })
+        ));
+    }
+
+    #[test]
+    fn test_line_ending_options() {
+        let path = temp_file("crlf", "\u{feff}first\r\nsecond\r\n".as_bytes());
+        let source = Source::File(path.clone());
+        let mut r = Reader::new();
+        assert_eq!(assert_ok!(r.read_source(source.clone())), "\u{feff}first\r");
+        let mut r = Reader::new()
+            .with_line_ending(LineEnding::CrLf)
+            .with_strip_bom(true);
+        assert_eq!(assert_ok!(r.read_source(source.clone())), "first");
+        assert_eq!(assert_ok!(r.read_source(source.clone())), "second");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_fd() {
+        let (read_fd, write_fd) = pipe_with(b"first\nsecond\n");
+        let spec = format!("fd:{read_fd}");
+        let mut r = Reader::new().with_close_fds(false);
+        assert_eq!(assert_ok!(r.read_pass_arg(&spec)), "first");
+        drop(r);
+        assert_ne!(unsafe { libc::fcntl(read_fd, libc::F_GETFD) }, -1);
+        let mut r = Reader::new();
+        assert_eq!(assert_ok!(r.read_pass_arg(&spec)), "second");
+        unsafe { libc::close(write_fd) };
+        assert!(matches!(
+            r.read_pass_arg(&spec),
+            Err(Error::UnexpectedEof { .. })
+        ));
+
+        for spec in ["fd:1", "fd:2", "fd:-1"] {
+            assert!(matches!(
+                spec.parse::<Source>(),
+                Err(Error::InvalidSpec { .. })
+            ));
+        }
+        assert!(matches!(
+            Reader::new().read_source(Source::Fd(2)),
+            Err(Error::InvalidSpec { .. })
+        ));
+        let e = Reader::new().read_pass_arg("fd:1000000").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "cannot read password from fd:1000000: bad file descriptor"
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        assert_eq!(fd_path(Path::new("/dev/stdin")), Some(0));
+        assert_eq!(fd_path(Path::new("/dev/fd/3")), Some(3));
+        assert_eq!(fd_path(Path::new("/proc/self/fd/4")), Some(4));
+        assert_eq!(fd_path(Path::new("/dev/fd")), None);
+        assert_eq!(fd_path(Path::new("/dev/fd/x")), None);
+
+        let (read_fd, write_fd) = pipe_with(b"a\nb\nc\n");
+        unsafe { libc::close(write_fd) };
+        let mut r = Reader::new();
+        for (spec, expected) in [
+            format!("fd:{read_fd}"),
+            format!("file:/dev/fd/{read_fd}"),
+            format!("file:/proc/self/fd/{read_fd}"),
+        ]
+        .iter()
+        .zip(["a", "b", "c"])
+        {
+            assert_eq!(assert_ok!(r.read_pass_arg(spec)), expected);
+        }
+
+        // A descriptor read through a path stays open.
+        let (read_fd, write_fd) = pipe_with(b"a\n");
+        unsafe { libc::close(write_fd) };
+        let mut r = Reader::new();
+        let spec = format!("file:/dev/fd/{read_fd}");
+        assert_eq!(assert_ok!(r.read_pass_arg(&spec)), "a");
+        drop(r);
+        assert_ne!(unsafe { libc::fcntl(read_fd, libc::F_GETFD) }, -1);
+        unsafe { libc::close(read_fd) };
+
+        let path = temp_file("alias", b"first\nsecond\n");
+        let link = path.with_extension("link");
+        let _ = std::fs::remove_file(&link);
+        std::fs::hard_link(&path, &link).unwrap();
+        let mut r = Reader::new();
+        assert_eq!(
+            assert_ok!(r.read_source(Source::File(path.clone()))),
+            "first"
+        );
+        assert_eq!(
+            assert_ok!(r.read_source(Source::File(link.clone()))),
+            "second"
+        );
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(link).unwrap();
+    }
+
+    #[test]
+    fn test_bytes() {
+        let path = temp_file("bytes", b"\xffhunter2\nsecond\n");
+        let source = Source::File(path.clone());
+        let mut r = Reader::new();
+        assert!(matches!(
+            r.read_source(source.clone()),
+            Err(Error::InvalidUtf8 { .. })
+        ));
+        assert_eq!(
+            assert_ok!(r.read_source_bytes(source.clone())).expose(),
+            b"second"
+        );
+
+        env::set_var(
+            "PASSARG_TEST_BYTES",
+            OsString::from_vec(b"\xffomg".to_vec()),
+        );
+        assert_eq!(
+            assert_ok!(r.read_source_os_string(Source::Env("PASSARG_TEST_BYTES".into()))),
+            OsString::from_vec(b"\xffomg".to_vec())
+        );
+        env::remove_var("PASSARG_TEST_BYTES");
+
+        let spec = format!("keyfile:{}", path.display());
+        for _ in 0..2 {
+            assert_eq!(
+                assert_ok!(r.read_pass_arg_bytes(&spec)).expose(),
+                b"\xffhunter2\nsecond\n"
+            );
+        }
+        let mut r = Reader::new().with_keyfile_limit(8);
+        match r.read_pass_arg_bytes(&spec) {
+            Err(e @ Error::TooLarge { limit: 8, .. }) => assert_eq!(e.kind(), ErrorKind::TooLarge),
+            result => panic!("unexpected {result:?}"),
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_os_str() {
+        let os = |bytes: &[u8]| OsString::from_vec(bytes.to_vec());
+        let spec = os(b"env:A\xff||file:/tmp/\xfe||keyfile:/\xfd||pass:omg");
+        let source = assert_ok!(Source::from_os_str(&spec));
+        assert_eq!(
+            source,
+            Source::Chain(vec![
+                Source::Env(os(b"A\xff")),
+                Source::File(os(b"/tmp/\xfe").into()),
+                Source::Keyfile(os(b"/\xfd").into()),
+                Source::Pass("omg".into()),
+            ])
+        );
+        assert_eq!(source.to_spec_os_string_unredacted(), spec);
+        assert_eq!(assert_ok!(Source::try_from(spec.as_os_str())), source);
+        assert_eq!(
+            assert_ok!(Source::from_os_str(OsStr::new("file:x"))),
+            Source::File("x".into())
+        );
+        for spec in [&b"pass:\xff"[..], b"exec:\xff", b"\xff"] {
+            match Source::from_os_str(&os(spec)) {
+                Err(Error::InvalidSpec { .. }) => {}
+                result => panic!("unexpected {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_slots() {
+        let path = temp_file("slots", b"first\nsecond\nthird\n");
+        let file = Source::File(path.clone());
+        let mut r = Reader::new();
+        assert_ok!(r.register_slot("in", file.clone()));
+        assert_ok!(r.register_slot("other", Source::Pass("omg".into())));
+        assert_ok!(r.register_slot("out", file.clone()));
+        assert!(matches!(
+            r.register_slot("in", Source::Stdin),
+            Err(Error::DuplicateSlot { .. })
+        ));
+        assert_eq!(assert_ok!(r.read_slot("out")).expose(), "second");
+        assert_eq!(assert_ok!(r.read_source(file)), "third");
+        assert_eq!(assert_ok!(r.read_slot("in")).expose(), "first");
+        assert_eq!(assert_ok!(r.read_slot("other")).expose(), "omg");
+        match r.read_slot("in") {
+            Err(e @ Error::SlotAlreadyRead { .. }) => assert_eq!(e.kind(), ErrorKind::InvalidSlot),
+            result => panic!("unexpected {result:?}"),
+        }
+        assert!(matches!(
+            r.read_slot("nope"),
+            Err(Error::UnknownSlot { .. })
+        ));
+        std::fs::remove_file(path).unwrap();
+
+        // Reading a slot opens no other slot's source,
+        // which could block on a FIFO without a writer or close an fd.
+        let fifo = env::temp_dir().join(format!("passarg-test-{}-slot-fifo", std::process::id()));
+        let c_path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let (read_fd, write_fd) = pipe_with(b"");
+        let mut r = Reader::new();
+        assert_ok!(r.register_slot("in", Source::File(fifo.clone())));
+        assert_ok!(r.register_slot("fd", Source::Fd(read_fd)));
+        assert_ok!(r.register_slot("out", Source::Pass("omg".into())));
+        assert_eq!(assert_ok!(r.read_slot("out")).expose(), "omg");
+        drop(r);
+        assert_ne!(unsafe { libc::fcntl(read_fd, libc::F_GETFD) }, -1);
+        unsafe { libc::close(read_fd) };
+        unsafe { libc::close(write_fd) };
+        std::fs::remove_file(fifo).unwrap();
+    }
+
+    #[test]
+    fn test_policy() {
+        let dir = env::temp_dir();
+        let path = temp_file("policy", b"hunter2\n");
+        let policy = Policy::new()
+            .allow([SourceKind::File, SourceKind::Prompt, SourceKind::Env])
+            .deny([SourceKind::Env])
+            .require_file_dir(&dir);
+        let mut r = Reader::new().with_policy(policy);
+        assert_eq!(
+            assert_ok!(r.read_source(Source::File(path.clone()))),
+            "hunter2"
+        );
+        for spec in [
+            "pass:omg",
+            "env:HOME",
+            "file:/etc/passwd",
+            "file:../x",
+            // Read as stdin and fd:5.
+            "file:/dev/stdin",
+            "file:/dev/fd/5",
+            // Checked before any link is read.
+            "file:/nonexistent||prompt||pass:omg",
+        ] {
+            match r.read_pass_arg(spec) {
+                Err(e @ Error::PolicyViolation { .. }) => {
+                    assert_eq!(e.kind(), ErrorKind::PolicyViolation);
+                    assert!(!e.to_string().contains("omg"));
+                }
+                result => panic!("unexpected {result:?} for {spec}"),
+            }
+        }
+        let missing = Source::File(dir.join("passarg-no-such-file"));
+        assert_eq!(
+            r.read_source(missing).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert!(matches!(
+            r.register_slot("in", Source::Stdin),
+            Err(Error::PolicyViolation { .. })
+        ));
+        // The path opened is checked again, in case a link on it changed.
+        let policy = Policy::new().require_file_dir(&dir);
+        let source = Source::File(path.clone());
+        let canonical = std::fs::canonicalize(&path).unwrap();
+        assert_ok!(policy.check_file_dir(&canonical, &source));
+        assert!(matches!(
+            policy.check_file_dir(Path::new("/etc/passwd"), &source),
+            Err(Error::PolicyViolation { .. })
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let set_mode = |path: &Path, mode| {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap()
+        };
+        let path = temp_file("perm", b"hunter2\n");
+        set_mode(&path, 0o644);
+        let mut r = Reader::new().with_permission_check(PermissionCheck::Strict);
+        match r.read_source(Source::File(path.clone())) {
+            Err(e @ Error::InsecureFile { .. }) => {
+                assert_eq!(e.kind(), ErrorKind::InsecureFile);
+                let message = e.to_string();
+                assert!(message.contains(&format!("{} (mode 0644)", path.display())));
+                assert!(message.ends_with("is accessible by group or others"));
+            }
+            result => panic!("unexpected {result:?}"),
+        }
+        assert!(matches!(
+            r.read_source(Source::Keyfile(path.clone())),
+            Err(Error::InsecureFile { .. })
+        ));
+
+        let warnings = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut r = Reader::new()
+            .with_permission_check(PermissionCheck::Warn)
+            .with_permission_warning({
+                let warnings = Arc::clone(&warnings);
+                move |e| warnings.lock().unwrap().push(e.to_string())
+            });
+        assert_eq!(
+            assert_ok!(r.read_source(Source::File(path.clone()))),
+            "hunter2"
+        );
+        assert_eq!(warnings.lock().unwrap().len(), 1);
+
+        set_mode(&path, 0o600);
+        let mut r = Reader::new().with_permission_check(PermissionCheck::Strict);
+        assert_eq!(
+            assert_ok!(r.read_source(Source::File(path.clone()))),
+            "hunter2"
+        );
+
+        // A symbolic link in a directory that others can replace it in.
+        let dir = env::temp_dir().join(format!("passarg-test-{}-perm-dir", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        set_mode(&dir, 0o777);
+        let link = dir.join("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        let mut r = Reader::new().with_permission_check(PermissionCheck::Strict);
+        match r.read_source(Source::File(link.clone())) {
+            Err(Error::InsecureFile { path, mode, .. }) => {
+                assert_eq!(path, dir);
+                assert_eq!(mode, 0o777);
+            }
+            result => panic!("unexpected {result:?}"),
+        }
+        set_mode(&dir, 0o755);
+        assert_eq!(
+            assert_ok!(r.read_source(Source::File(link.clone()))),
+            "hunter2"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_custom_scheme() {
+        struct Vault(HashMap<&'static str, &'static str>);
+
+        impl SourceProvider for Vault {
+            fn read(&self, source: &Source, _: &mut Reader<'_>) -> Result<SecretBytes, Error> {
+                let Source::Custom { rest, .. } = source else {
+                    panic!("unexpected {source:?}");
+                };
+                match self.0.get(rest.as_str()) {
+                    Some(password) => Ok(password.as_bytes().to_vec().into()),
+                    None => Err(Error::KeyNotFound {
+                        spec: source.clone(),
+                    }),
+                }
+            }
+        }
+
+        // Reads the password from the source named by the rest of the argument.
+        struct Indirect;
+
+        impl SourceProvider for Indirect {
+            fn parse(&self, _: &str, rest: Option<&str>) -> Result<Source, Error> {
+                Ok(Source::Chain(vec![rest.unwrap_or_default().parse()?]))
+            }
+
+            fn read(&self, source: &Source, reader: &mut Reader<'_>) -> Result<SecretBytes, Error> {
+                reader.read_source_bytes(source.clone())
+            }
+        }
+
+        assert!(matches!(
+            "vault:db".parse::<Source>(),
+            Err(Error::InvalidSpec { .. })
+        ));
+        let mut r = Reader::new()
+            .with_scheme("vault", Vault(HashMap::from([("db", "hunter2")])))
+            .with_scheme("indirect", Indirect);
+        let source = assert_ok!(r.parse_pass_arg("vault:db"));
+        assert_eq!(
+            source,
+            Source::Custom {
+                scheme: "vault".into(),
+                rest: "db".into(),
+            }
+        );
+        assert_eq!(source.to_string(), "vault:db");
+        assert_eq!(assert_ok!(r.read_pass_arg("vault:db")), "hunter2");
+        assert_eq!(
+            assert_ok!(r.read_pass_arg("vault:missing||pass:omg")),
+            "omg"
+        );
+        assert_eq!(assert_ok!(r.read_pass_arg("indirect:pass:omg")), "omg");
+        // Built-in schemes still parse to their own variants.
+        assert_eq!(
+            assert_ok!(r.parse_pass_arg("file:/x")),
+            Source::File("/x".into())
+        );
+        match r.read_pass_arg("corp:db") {
+            Err(e @ Error::InvalidSpec { .. }) => {
+                assert_eq!(
+                    e.to_string(),
+                    r#"invalid password argument corp:<redacted>: unknown type "corp""#
+                );
+            }
+            result => panic!("unexpected {result:?}"),
+        }
+        // A provider registered for a built-in scheme also reads sources parsed on their own.
+        let mut r = Reader::new().with_scheme("file", Vault(HashMap::from([("/x", "hunter2")])));
+        assert_eq!(assert_ok!(r.read_pass_arg("file:/x")), "hunter2");
+        let source: Source = assert_ok!("file:/x".parse());
+        assert_eq!(assert_ok!(r.read_source(source)), "hunter2");
+        // A custom source of a built-in scheme with an empty rest has none.
+        assert_eq!(assert_ok!(Source::parse_custom("stdin", "")), Source::Stdin);
+        assert_eq!(
+            assert_ok!(Source::parse_custom("prompt", "")),
+            Source::Prompt("Password: ".into())
+        );
+        assert_eq!(
+            assert_ok!(Source::parse_custom("pass", "")),
+            Source::Pass("".into())
+        );
+        let policy = Policy::new().deny([SourceKind::Custom]);
+        let mut r = Reader::new()
+            .with_scheme("vault", Vault(HashMap::new()))
+            .with_policy(policy);
+        assert!(matches!(
+            r.read_pass_arg("vault:db"),
+            Err(Error::PolicyViolation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let timeout = std::time::Duration::from_millis(50);
+        // Timing out partway through a line keeps what was read of it.
+        let (read_fd, write_fd) = pipe_with(b"hun");
+        let mut r = Reader::new().with_timeout(timeout);
+        match r.read_source(Source::Fd(read_fd)) {
+            Err(e @ Error::Timeout { .. }) => {
+                assert_eq!(e.kind(), ErrorKind::TimedOut);
+                assert_eq!(
+                    e.to_string(),
+                    format!("cannot read password from fd:{read_fd}: timed out after 50ms")
+                );
+            }
+            result => panic!("unexpected {result:?}"),
+        }
+        let written = unsafe { libc::write(write_fd, b"ter2\n".as_ptr().cast(), 5) };
+        assert_eq!(written, 5);
+        assert_eq!(assert_ok!(r.read_source(Source::Fd(read_fd))), "hunter2");
+        unsafe { libc::close(write_fd) };
+
+        // A FIFO that no writer ever opens.
+        let path = env::temp_dir().join(format!("passarg-test-{}-fifo", std::process::id()));
+        let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        assert!(matches!(
+            r.read_source(Source::File(path.clone())),
+            Err(Error::Timeout { .. })
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_remove_env() {
+        env::set_var("PASSARG_TEST_REMOVE_ENV", "hunter2");
+        env::set_var("PASSARG_TEST_KEEP_ENV", "omg");
+        let mut r = Reader::new();
+        assert_eq!(
+            assert_ok!(r.read_pass_arg("env:PASSARG_TEST_KEEP_ENV")),
+            "omg"
+        );
+        assert!(env::var_os("PASSARG_TEST_KEEP_ENV").is_some());
+        let mut r = Reader::new().with_remove_env(true);
+        assert_eq!(
+            assert_ok!(r.read_pass_arg("env:PASSARG_TEST_REMOVE_ENV")),
+            "hunter2"
+        );
+        assert!(env::var_os("PASSARG_TEST_REMOVE_ENV").is_none());
+        assert!(matches!(
+            r.read_pass_arg("env:PASSARG_TEST_REMOVE_ENV"),
+            Err(Error::EnvVar { .. })
+        ));
+        env::remove_var("PASSARG_TEST_KEEP_ENV");
+    }
+
+    #[test]
+    fn test_file_once() {
+        assert_eq!(
+            assert_ok!("file-once:/x".parse::<Source>()),
+            Source::FileOnce("/x".into())
+        );
+        let path = temp_file("once", b"first\nsecond\n");
+        let spec = format!("file-once:{}", path.display());
+        let mut r = Reader::new();
+        assert_eq!(assert_ok!(r.read_pass_arg(&spec)), "first");
+        assert!(!path.exists());
+        // Later lines come from the open stream.
+        assert_eq!(assert_ok!(r.read_pass_arg(&spec)), "second");
+        assert!(matches!(
+            r.read_pass_arg(&spec),
+            Err(Error::UnexpectedEof { .. })
+        ));
+        // A new file at the same path is read afresh.
+        std::fs::write(&path, b"third\n").unwrap();
+        let file = format!("file:{}", path.display());
+        assert_eq!(assert_ok!(r.read_pass_arg(&file)), "third");
+        assert!(path.exists());
+        assert_ok!(r.finish());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_file_once_unlink() {
+        // Files under /proc cannot be removed, even by root.
+        let mut r = Reader::new();
+        assert_ok!(r.read_pass_arg("file-once:/proc/self/comm"));
+        match r.finish() {
+            Err(Error::Unlink { spec, .. }) => {
+                assert_eq!(spec, Source::FileOnce("/proc/self/comm".into()))
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
     }
 }
@@ -0,0 +1,511 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, StdinLock};
+use std::mem::ManuallyDrop;
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::Path;
+use std::time::Instant;
+use zeroize::Zeroize;
+
+use crate::files::FileStream;
+
+const BUF_SIZE: usize = 4096;
+
+const BOM: &[u8] = "\u{feff}".as_bytes();
+
+/// Line endings recognized in file-like sources.
+///
+/// The line ending is removed from the line read.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Lines end with LF (`\n`), as with OpenSSL.
+    #[default]
+    Lf,
+    /// Lines end with LF (`\n`) or CR LF (`\r\n`).
+    CrLf,
+    /// Lines end with CR (`\r`).
+    Cr,
+    /// Lines end with LF (`\n`), CR LF (`\r\n`) or CR (`\r`).
+    Any,
+}
+
+/// How lines are read from file-like sources.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct LineOptions {
+    pub(crate) ending: LineEnding,
+    pub(crate) strip_bom: bool,
+    pub(crate) eof_as_empty: bool,
+}
+
+impl LineOptions {
+    /// Removes the line ending, if any, from the end of `line`.
+    pub(crate) fn trim_ending(&self, line: &mut Vec<u8>) {
+        let mut strip = |b| {
+            let found = line.last() == Some(&b);
+            if found {
+                line.pop();
+            }
+            found
+        };
+        match self.ending {
+            LineEnding::Lf => _ = strip(b'\n'),
+            LineEnding::CrLf => _ = strip(b'\n') && strip(b'\r'),
+            LineEnding::Cr => _ = strip(b'\r'),
+            LineEnding::Any => _ = strip(b'\n') || strip(b'\r'),
+        }
+    }
+}
+
+/// Line reader that scrubs its buffer.
+///
+/// Works like [`std::io::BufRead::read_line()`] on a [`std::io::BufReader`],
+/// except that bytes are zeroed in the buffer as soon as they are handed out,
+/// and whatever remains buffered is zeroed on drop.
+///
+/// A line is handed out only once read in full:
+/// if reading fails partway, such as on a timeout,
+/// the part read so far is kept for the next call to finish.
+pub(crate) struct LineReader<R> {
+    inner: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    end: usize,
+    /// The line being read.
+    partial: Vec<u8>,
+    at_start: bool,
+    skip_lf: bool,
+}
+
+impl<R> LineReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self::with_capacity(BUF_SIZE, inner)
+    }
+
+    pub(crate) fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+            partial: Vec::new(),
+            at_start: true,
+            skip_lf: false,
+        }
+    }
+}
+
+impl<R: Read> LineReader<R> {
+    /// Appends the next line, including its line ending if any, to `line`.
+    /// Returns the number of bytes read, which is 0 at end of file.
+    ///
+    /// A CR LF pair counts as one line ending even with [`LineEnding::Any`],
+    /// where a lone CR also ends a line.
+    /// A UTF-8 byte order mark at the start of the stream is removed
+    /// if `options.strip_bom` is set.
+    pub(crate) fn read_line(
+        &mut self,
+        line: &mut Vec<u8>,
+        options: &LineOptions,
+    ) -> io::Result<usize> {
+        self.read_line_with(line, options, |_| Ok(()))
+    }
+
+    /// Same as [`LineReader::read_line()`],
+    /// but calls `wait` with the inner reader before each read from it.
+    pub(crate) fn read_line_with(
+        &mut self,
+        line: &mut Vec<u8>,
+        options: &LineOptions,
+        wait: impl FnMut(&R) -> io::Result<()>,
+    ) -> io::Result<usize> {
+        self.read_until_ending(options.ending, wait)?;
+        Ok(self.take_line(line, options))
+    }
+
+    /// Appends the rest of the stream to `buf`.
+    pub(crate) fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        while self.read_until_ending(LineEnding::Lf, |_| Ok(()))? {}
+        let total = self.partial.len();
+        extend_zeroizing(buf, &self.partial);
+        self.partial.zeroize();
+        Ok(total)
+    }
+
+    /// Reads up to the end of the line into `self.partial`.
+    /// Returns whether a line ending was found, as opposed to the end of file.
+    fn read_until_ending(
+        &mut self,
+        ending: LineEnding,
+        mut wait: impl FnMut(&R) -> io::Result<()>,
+    ) -> io::Result<bool> {
+        loop {
+            if self.pos == self.end {
+                self.pos = 0;
+                self.end = 0;
+                wait(&self.inner)?;
+                let n = loop {
+                    match self.inner.read(&mut self.buf) {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        result => break result?,
+                    }
+                };
+                if n == 0 {
+                    return Ok(false);
+                }
+                self.end = n;
+            }
+            if self.take_buffered(ending) {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl LineReader<crate::async_reader::AsyncStream> {
+    /// Same as [`LineReader::read_line()`], but reads asynchronously.
+    ///
+    /// Fails with [`io::ErrorKind::TimedOut`] if no line is read by `deadline`.
+    /// Nothing is lost if the returned future is dropped before it completes.
+    pub(crate) async fn read_line_async(
+        &mut self,
+        line: &mut Vec<u8>,
+        options: &LineOptions,
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
+        loop {
+            if self.pos == self.end {
+                self.pos = 0;
+                self.end = 0;
+                let n = self.inner.read(&mut self.buf, deadline).await?;
+                if n == 0 {
+                    break;
+                }
+                self.end = n;
+            }
+            if self.take_buffered(options.ending) {
+                break;
+            }
+        }
+        Ok(self.take_line(line, options))
+    }
+}
+
+impl<R> LineReader<R> {
+    /// Moves buffered bytes, up to and including the first line ending, to `self.partial`.
+    /// Returns whether a line ending was found.
+    /// The buffer must not be empty.
+    fn take_buffered(&mut self, ending: LineEnding) -> bool {
+        let is_ending = |b: u8| match ending {
+            LineEnding::Lf | LineEnding::CrLf => b == b'\n',
+            LineEnding::Cr => b == b'\r',
+            LineEnding::Any => b == b'\n' || b == b'\r',
+        };
+        if std::mem::take(&mut self.skip_lf) && self.buf[self.pos] == b'\n' {
+            self.buf[self.pos] = 0;
+            self.pos += 1;
+            return false;
+        }
+        let avail = &mut self.buf[self.pos..self.end];
+        let (len, done) = match avail.iter().position(|&b| is_ending(b)) {
+            Some(i) => (i + 1, true),
+            None => (avail.len(), false),
+        };
+        self.skip_lf = ending == LineEnding::Any && done && avail[len - 1] == b'\r';
+        extend_zeroizing(&mut self.partial, &avail[..len]);
+        avail[..len].zeroize();
+        self.pos += len;
+        done
+    }
+
+    /// Moves the line read in full from `self.partial` to `line`,
+    /// removing the byte order mark if it is the first line and `options.strip_bom` is set.
+    /// Returns the length of the line as read, which is 0 at end of file.
+    fn take_line(&mut self, line: &mut Vec<u8>, options: &LineOptions) -> usize {
+        let total = self.partial.len();
+        let mut read = &self.partial[..];
+        if std::mem::take(&mut self.at_start) && options.strip_bom && read.starts_with(BOM) {
+            read = &read[BOM.len()..];
+        }
+        extend_zeroizing(line, read);
+        self.partial.zeroize();
+        total
+    }
+}
+
+impl<R> Drop for LineReader<R> {
+    fn drop(&mut self) {
+        self.buf.zeroize();
+        self.partial.zeroize();
+    }
+}
+
+/// Appends `bytes` to `v`, zeroing the old allocation of `v` if it has to grow.
+pub(crate) fn extend_zeroizing(v: &mut Vec<u8>, bytes: &[u8]) {
+    if v.capacity() - v.len() < bytes.len() {
+        let mut grown = Vec::with_capacity((v.len() + bytes.len()).max(v.capacity() * 2));
+        grown.extend_from_slice(v);
+        v.zeroize();
+        *v = grown;
+    }
+    v.extend_from_slice(bytes);
+}
+
+/// Opens `path` for reading, without waiting for a writer if it is a FIFO.
+pub(crate) fn open_without_waiting(path: &Path) -> io::Result<File> {
+    let f = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+    let flags = unsafe { libc::fcntl(f.as_raw_fd(), libc::F_GETFL) };
+    if flags == -1
+        || unsafe { libc::fcntl(f.as_raw_fd(), libc::F_SETFL, flags & !libc::O_NONBLOCK) } == -1
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(f)
+}
+
+/// Waits until `fd` is readable, failing with [`io::ErrorKind::TimedOut`]
+/// if it is not by `deadline`.
+pub(crate) fn wait_readable(fd: RawFd, deadline: Instant) -> io::Result<()> {
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let ms = left.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32;
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pollfd, 1, ms) } {
+            -1 => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+            0 => return Err(io::ErrorKind::TimedOut.into()),
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Standard input, read directly from file descriptor 0.
+///
+/// This bypasses the buffer of [`std::io::Stdin`],
+/// which we can neither scrub nor leave unread bytes in;
+/// the stdin lock is held nonetheless so that nothing else reads stdin meanwhile.
+pub(crate) struct RawStdin<'a> {
+    _lock: StdinLock<'a>,
+    file: ManuallyDrop<File>,
+}
+
+impl RawStdin<'_> {
+    pub(crate) fn new() -> Self {
+        Self {
+            _lock: io::stdin().lock(),
+            file: ManuallyDrop::new(unsafe { File::from_raw_fd(0) }),
+        }
+    }
+}
+
+impl AsRawFd for RawStdin<'_> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl Read for RawStdin<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+/// Device and inode numbers of an open file.
+pub(crate) type FileId = (u64, u64);
+
+/// Returns the [`FileId`] of the file open as `fd`,
+/// failing with `EBADF` if `fd` is not an open file descriptor.
+pub(crate) fn file_id(fd: RawFd) -> io::Result<FileId> {
+    // The number is not known to be open yet, so it must not become a `File`.
+    let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+    if unsafe { libc::fstat(fd, stat.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    // The field types vary by platform; `MetadataExt` widens them the same way.
+    #[allow(clippy::unnecessary_cast)]
+    Ok((stat.st_dev as u64, stat.st_ino as u64))
+}
+
+/// Returns the ID of the file at `path`, without opening it.
+pub(crate) fn path_id(path: &Path) -> io::Result<FileId> {
+    let metadata = std::fs::metadata(path)?;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+/// Open file read by a file-like source.
+pub(crate) enum Stream<'a> {
+    File(File),
+    Fd(FdFile),
+    Stdin(RawStdin<'a>),
+}
+
+impl FileStream for Stream<'_> {
+    fn from_file(file: File) -> io::Result<Self> {
+        Ok(Stream::File(file))
+    }
+
+    fn from_fd(fd: RawFd, owned: bool) -> io::Result<Self> {
+        Ok(match fd {
+            0 => Stream::Stdin(RawStdin::new()),
+            fd => Stream::Fd(FdFile::new(fd, owned)?),
+        })
+    }
+
+    fn is_private(&self) -> bool {
+        match self {
+            Stream::File(_) => true,
+            Stream::Fd(f) => f.is_owned(),
+            Stream::Stdin(_) => false,
+        }
+    }
+}
+
+impl AsRawFd for Stream<'_> {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Stream::File(f) => f.as_raw_fd(),
+            Stream::Fd(f) => f.file.as_raw_fd(),
+            Stream::Stdin(f) => f.as_raw_fd(),
+        }
+    }
+}
+
+impl Read for Stream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::File(f) => f.read(buf),
+            Stream::Fd(f) => f.read(buf),
+            Stream::Stdin(f) => f.read(buf),
+        }
+    }
+}
+
+/// File descriptor read by an **fd:** source.
+///
+/// The descriptor is closed on drop only if owned.
+pub(crate) struct FdFile {
+    file: ManuallyDrop<File>,
+    owned: bool,
+}
+
+impl FdFile {
+    /// Wraps `fd`, failing with `EBADF` if it is not an open file descriptor.
+    pub(crate) fn new(fd: RawFd, owned: bool) -> io::Result<Self> {
+        if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            file: ManuallyDrop::new(unsafe { File::from_raw_fd(fd) }),
+            owned,
+        })
+    }
+
+    /// Returns whether the descriptor is closed on drop.
+    pub(crate) fn is_owned(&self) -> bool {
+        self.owned
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn as_file(&self) -> &File {
+        &self.file
+    }
+}
+
+impl Read for FdFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Drop for FdFile {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { ManuallyDrop::drop(&mut self.file) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_line() {
+        let options = LineOptions::default();
+        let mut r = LineReader::with_capacity(4, &b"first\nsecond\nlast"[..]);
+        let mut line = Vec::new();
+        assert_eq!(r.read_line(&mut line, &options).unwrap(), 6);
+        assert_eq!(line, b"first\n");
+        assert!(r.buf[..r.pos].iter().all(|&b| b == 0));
+        line.clear();
+        assert_eq!(r.read_line(&mut line, &options).unwrap(), 7);
+        assert_eq!(line, b"second\n");
+        line.clear();
+        assert_eq!(r.read_line(&mut line, &options).unwrap(), 4);
+        assert_eq!(line, b"last");
+        line.clear();
+        assert_eq!(r.read_line(&mut line, &options).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let input = "\u{feff}a\r\nb\rc\nd\r".as_bytes();
+        let cases: [(LineEnding, &[&str]); 4] = [
+            (LineEnding::Lf, &["\u{feff}a\r", "b\rc", "d\r"]),
+            (LineEnding::CrLf, &["\u{feff}a", "b\rc", "d\r"]),
+            (LineEnding::Cr, &["\u{feff}a", "\nb", "c\nd"]),
+            (LineEnding::Any, &["\u{feff}a", "b", "c", "d"]),
+        ];
+        for (ending, expected) in cases {
+            for strip_bom in [false, true] {
+                for capacity in [1, 4096] {
+                    let options = LineOptions {
+                        ending,
+                        strip_bom,
+                        ..Default::default()
+                    };
+                    let mut r = LineReader::with_capacity(capacity, input);
+                    let mut lines = Vec::new();
+                    loop {
+                        let mut line = Vec::new();
+                        if r.read_line(&mut line, &options).unwrap() == 0 {
+                            break;
+                        }
+                        options.trim_ending(&mut line);
+                        lines.push(String::from_utf8(line).unwrap());
+                    }
+                    let mut expected = expected.to_vec();
+                    let first = expected[0].trim_start_matches('\u{feff}');
+                    if strip_bom {
+                        expected[0] = first;
+                    }
+                    assert_eq!(lines, expected, "{ending:?} strip_bom={strip_bom}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_file_id() {
+        let file = File::open("/dev/null").unwrap();
+        assert_eq!(
+            file_id(file.as_raw_fd()).unwrap(),
+            path_id(Path::new("/dev/null")).unwrap()
+        );
+        for fd in [-1, 1_000_000] {
+            assert_eq!(file_id(fd).unwrap_err().raw_os_error(), Some(libc::EBADF));
+        }
+    }
+}
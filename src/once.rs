@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::line::FileId;
+use crate::{Error, Source};
+
+/// Files of **file-once:** sources, removed after their first line is read.
+///
+/// The stream of a removed file stays open, so its later lines can still be read.
+/// Files that could not be removed are retried by [`OnceFiles::finish()`],
+/// and when dropped.
+#[derive(Default)]
+pub(crate) struct OnceFiles {
+    removed: HashMap<PathBuf, FileId>,
+    failed: Vec<Source>,
+}
+
+impl OnceFiles {
+    /// Returns the stream of `path` if it was removed.
+    pub(crate) fn removed(&self, path: &Path) -> Option<FileId> {
+        self.removed.get(path).copied()
+    }
+
+    /// Removes `path`, read through the stream `id`, unless already removed.
+    ///
+    /// Returns whether `path` is gone; on failure, `source` is kept to be retried.
+    pub(crate) fn remove(&mut self, path: &Path, id: FileId, source: &Source) -> bool {
+        if self.removed.contains_key(path) {
+            return true;
+        }
+        if unlink(path).is_err() {
+            if !self.failed.contains(source) {
+                self.failed.push(source.clone());
+            }
+            return false;
+        }
+        self.failed.retain(|failed| failed != source);
+        self.removed.insert(path.into(), id);
+        true
+    }
+
+    /// Retries removing the files that could not be removed,
+    /// returning the error of the first that still cannot be.
+    pub(crate) fn finish(&mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+        for source in std::mem::take(&mut self.failed) {
+            let Source::FileOnce(path) = &source else {
+                continue;
+            };
+            if let Err(error) = unlink(path) {
+                if result.is_ok() {
+                    result = Err(Error::Unlink {
+                        spec: source.clone(),
+                        error,
+                    });
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Drop for OnceFiles {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Removes `path`; one already gone counts as removed.
+fn unlink(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
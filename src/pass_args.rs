@@ -0,0 +1,74 @@
+use std::ffi::OsString;
+
+use crate::{Error, Reader, SecretString, Source};
+
+/// A set of passphrase arguments, read together in a fixed order.
+///
+/// With the `derive` feature enabled, `#[derive(PassArgs)]` implements this
+/// for a struct whose passphrase argument fields are annotated with
+/// `#[passarg(order = N)]`, or `#[passarg(order = N, default = "spec")]`;
+/// see the [package documentation](crate#declaring-passphrase-arguments).
+pub trait PassArgs {
+    /// The passwords read.
+    type Secrets;
+
+    /// Reads all passwords, in the declared order.
+    fn read_all(&self, reader: &mut Reader<'_>) -> Result<Self::Secrets, Error>;
+}
+
+/// Field types that hold a passphrase argument.
+///
+/// Arguments given as strings are parsed with the schemes registered with `reader`.
+pub trait SpecField {
+    fn to_source(&self, reader: &Reader<'_>) -> Result<Source, Error>;
+}
+
+impl SpecField for Source {
+    fn to_source(&self, _: &Reader<'_>) -> Result<Source, Error> {
+        Ok(self.clone())
+    }
+}
+
+impl SpecField for String {
+    fn to_source(&self, reader: &Reader<'_>) -> Result<Source, Error> {
+        reader.parse_pass_arg(self)
+    }
+}
+
+impl SpecField for OsString {
+    fn to_source(&self, reader: &Reader<'_>) -> Result<Source, Error> {
+        match self.to_str() {
+            Some(s) => reader.parse_pass_arg(s),
+            None => Source::from_os_str(self),
+        }
+    }
+}
+
+pub fn read_required<T: SpecField>(
+    reader: &mut Reader<'_>,
+    field: &T,
+) -> Result<SecretString, Error> {
+    let source = field.to_source(reader)?;
+    reader.read_source_secret(source)
+}
+
+pub fn read_optional<T: SpecField>(
+    reader: &mut Reader<'_>,
+    field: &Option<T>,
+) -> Result<Option<SecretString>, Error> {
+    field
+        .as_ref()
+        .map(|field| read_required(reader, field))
+        .transpose()
+}
+
+pub fn read_defaulted<T: SpecField>(
+    reader: &mut Reader<'_>,
+    field: &Option<T>,
+    default: &str,
+) -> Result<SecretString, Error> {
+    match field {
+        Some(field) => read_required(reader, field),
+        None => reader.read_pass_arg_secret(default),
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs::{self, File};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Source};
+
+/// How [`Reader`](crate::Reader) checks that files holding passwords are private,
+/// like `ssh(1)` does for private keys.
+///
+/// A **file:**, **file-once:** or **keyfile:** source that is a regular file is insecure
+/// if it is accessible by group or others, if it is owned by another user (other than root),
+/// or if its path goes through a symbolic link in a directory
+/// that group or others may write to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionCheck {
+    /// No checks.
+    #[default]
+    Off,
+    /// Insecure files are reported to the warning callback
+    /// (see [`Reader::with_permission_warning()`](crate::Reader::with_permission_warning)),
+    /// then read anyway.
+    Warn,
+    /// Insecure files fail with [`Error::InsecureFile`].
+    Strict,
+}
+
+/// Checks `file`, opened from `path`, returning [`Error::InsecureFile`] if it is insecure.
+pub(crate) fn check_file(path: &Path, file: &File, spec: &Source) -> Result<(), Error> {
+    let insecure = |path: PathBuf, mode: u32, reason: &str| Error::InsecureFile {
+        spec: spec.clone(),
+        path,
+        mode: mode & 0o7777,
+        reason: reason.into(),
+    };
+    let metadata = file.metadata().map_err(|error| Error::Io {
+        spec: spec.clone(),
+        error,
+    })?;
+    if !metadata.is_file() {
+        return Ok(());
+    }
+    if metadata.mode() & 0o077 != 0 {
+        return Err(insecure(
+            path.into(),
+            metadata.mode(),
+            "accessible by group or others",
+        ));
+    }
+    let uid = unsafe { libc::geteuid() };
+    if metadata.uid() != uid && metadata.uid() != 0 {
+        return Err(insecure(
+            path.into(),
+            metadata.mode(),
+            "owned by another user",
+        ));
+    }
+    let mut prefix = PathBuf::new();
+    for component in path.components() {
+        prefix.push(component);
+        let Ok(link) = fs::symlink_metadata(&prefix) else {
+            break;
+        };
+        if !link.file_type().is_symlink() {
+            continue;
+        }
+        let dir = prefix.parent().unwrap_or(Path::new("."));
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let Ok(dir_metadata) = fs::metadata(dir) else {
+            continue;
+        };
+        let mode = dir_metadata.permissions().mode();
+        // In a sticky directory, only the owner of the link can replace it.
+        let protected = mode & 0o1000 != 0 && (link.uid() == uid || link.uid() == 0);
+        if mode & 0o022 != 0 && !protected {
+            return Err(insecure(
+                dir.into(),
+                mode,
+                "holds a symbolic link on the path, and is writable by group or others",
+            ));
+        }
+    }
+    Ok(())
+}
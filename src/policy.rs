@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{fd_path, Error, Source};
+
+/// Kind of a [`Source`], named after its scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SourceKind {
+    /// **pass:**
+    Pass,
+    /// **env:**
+    Env,
+    /// **file:**
+    File,
+    /// **file-once:**
+    FileOnce,
+    /// **fd:**
+    Fd,
+    /// **stdin**
+    Stdin,
+    /// **prompt**
+    Prompt,
+    /// **prompt-new**
+    PromptNew,
+    /// **exec:** and **cmd:**
+    Exec,
+    /// **sh:**
+    Shell,
+    /// **keyring:** and **keyctl:**
+    Keyring,
+    /// **cred:**
+    Credential,
+    /// **keyfile:**
+    Keyfile,
+    /// Any custom scheme; see [`SourceProvider`](crate::SourceProvider).
+    Custom,
+}
+
+impl SourceKind {
+    /// Returns the kind of `source`, or `None` for a [`Source::Chain`].
+    ///
+    /// A **file:** or **file-once:** path that names a file descriptor,
+    /// such as `/dev/stdin` or `/dev/fd/3`, is of the kind it is read as,
+    /// [`SourceKind::Stdin`] or [`SourceKind::Fd`].
+    pub fn of(source: &Source) -> Option<Self> {
+        Some(match source {
+            Source::File(path) | Source::FileOnce(path) if fd_path(path) == Some(0) => Self::Stdin,
+            Source::File(path) | Source::FileOnce(path) if fd_path(path).is_some() => Self::Fd,
+            Source::Pass(_) => Self::Pass,
+            Source::Env(_) => Self::Env,
+            Source::File(_) => Self::File,
+            Source::FileOnce(_) => Self::FileOnce,
+            Source::Fd(_) => Self::Fd,
+            Source::Stdin => Self::Stdin,
+            Source::Prompt(_) => Self::Prompt,
+            Source::PromptNew(_) => Self::PromptNew,
+            Source::Exec(_) => Self::Exec,
+            Source::Shell(_) => Self::Shell,
+            Source::Keyring { .. } => Self::Keyring,
+            Source::Credential(_) => Self::Credential,
+            Source::Keyfile(_) => Self::Keyfile,
+            Source::Custom { .. } => Self::Custom,
+            Source::Chain(_) => return None,
+        })
+    }
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "pass",
+            Self::Env => "env",
+            Self::File => "file",
+            Self::FileOnce => "file-once",
+            Self::Fd => "fd",
+            Self::Stdin => "stdin",
+            Self::Prompt => "prompt",
+            Self::PromptNew => "prompt-new",
+            Self::Exec => "exec",
+            Self::Shell => "sh",
+            Self::Keyring => "keyring",
+            Self::Credential => "cred",
+            Self::Keyfile => "keyfile",
+            Self::Custom => "custom",
+        })
+    }
+}
+
+/// Restrictions on the sources a [`Reader`](crate::Reader) reads from.
+///
+/// A source that violates the policy fails with [`Error::PolicyViolation`]
+/// before anything is read or opened.
+/// Every link of a [`Source::Chain`] must comply,
+/// even those that would not be reached.
+/// The default policy allows every source.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    allowed: Option<HashSet<SourceKind>>,
+    denied: HashSet<SourceKind>,
+    file_dirs: Vec<PathBuf>,
+}
+
+impl Policy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows only the given kinds, and those allowed by earlier calls.
+    pub fn allow(mut self, kinds: impl IntoIterator<Item = SourceKind>) -> Self {
+        self.allowed.get_or_insert_with(HashSet::new).extend(kinds);
+        self
+    }
+
+    /// Denies the given kinds, even if allowed.
+    pub fn deny(mut self, kinds: impl IntoIterator<Item = SourceKind>) -> Self {
+        self.denied.extend(kinds);
+        self
+    }
+
+    /// Requires **file:**, **file-once:** and **keyfile:** paths to be under `dir`,
+    /// or under a directory given in another call.
+    ///
+    /// Symbolic links are resolved before the check,
+    /// so that a link cannot point outside the directories.
+    pub fn require_file_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.file_dirs.push(dir.into());
+        self
+    }
+
+    pub(crate) fn check(&self, source: &Source) -> Result<(), Error> {
+        let Some(kind) = SourceKind::of(source) else {
+            if let Source::Chain(links) = source {
+                return links.iter().try_for_each(|link| self.check(link));
+            }
+            unreachable!("only chains have no kind");
+        };
+        let violation = |reason: String| Error::PolicyViolation {
+            spec: source.clone(),
+            reason,
+        };
+        if self.denied.contains(&kind) || self.allowed.as_ref().is_some_and(|a| !a.contains(&kind))
+        {
+            return Err(violation(format!("{kind} sources are not allowed")));
+        }
+        if let Source::File(path) | Source::FileOnce(path) | Source::Keyfile(path) = source {
+            // Paths that name a file descriptor are checked as such, above.
+            let named_fd = matches!(kind, SourceKind::Fd | SourceKind::Stdin);
+            if !named_fd
+                && !self.file_dirs.is_empty()
+                && !self.file_dirs.iter().any(|dir| is_under(path, dir))
+            {
+                return Err(violation(OUTSIDE_FILE_DIRS.into()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that `canonical`, the resolved path of a file about to be opened,
+    /// is under the directories required by [`Policy::require_file_dir()`].
+    ///
+    /// [`Policy::check()`] checks the path as given, before anything is read;
+    /// this checks the path that is actually opened,
+    /// in case a symbolic link on it changed meanwhile.
+    pub(crate) fn check_file_dir(&self, canonical: &Path, source: &Source) -> Result<(), Error> {
+        if self.file_dirs.is_empty()
+            || self
+                .file_dirs
+                .iter()
+                .any(|dir| resolve(dir).is_some_and(|dir| canonical.starts_with(dir)))
+        {
+            return Ok(());
+        }
+        Err(Error::PolicyViolation {
+            spec: source.clone(),
+            reason: OUTSIDE_FILE_DIRS.into(),
+        })
+    }
+}
+
+const OUTSIDE_FILE_DIRS: &str = "file is outside the allowed directories";
+
+/// Returns whether `path` is `dir` or below it.
+///
+/// Paths that do not exist (yet) are compared as given,
+/// and must be absolute and free of `..` components.
+fn is_under(path: &Path, dir: &Path) -> bool {
+    match (resolve(path), resolve(dir)) {
+        (Some(path), Some(dir)) => path.starts_with(dir),
+        _ => false,
+    }
+}
+
+/// Resolves `p` for [`is_under()`].
+fn resolve(p: &Path) -> Option<PathBuf> {
+    std::fs::canonicalize(p).ok().or_else(|| {
+        let lexical = p.is_absolute() && p.components().all(|c| !matches!(c, Component::ParentDir));
+        lexical.then(|| p.to_path_buf())
+    })
+}
@@ -0,0 +1,66 @@
+use crate::{Error, Reader, SecretBytes, Source};
+
+/// Schemes of the built-in sources.
+pub(crate) const BUILTIN_SCHEMES: &[&str] = &[
+    "pass",
+    "env",
+    "file",
+    "file-once",
+    "fd",
+    "stdin",
+    "prompt",
+    "prompt-new",
+    "exec",
+    "cmd",
+    "sh",
+    "keyring",
+    "keyctl",
+    "cred",
+    "keyfile",
+];
+
+/// Provider of passwords for a scheme, such as **vault:**.
+///
+/// Register a provider with [`Reader::with_scheme()`];
+/// [`Reader::read_pass_arg()`] and [`Reader::parse_pass_arg()`]
+/// then accept arguments of its scheme, also as links of a chain.
+///
+/// ```rust
+/// use passarg::{Error, Reader, SecretBytes, Source, SourceProvider};
+///
+/// struct Upper;
+///
+/// impl SourceProvider for Upper {
+///     fn read(&self, source: &Source, _: &mut Reader<'_>) -> Result<SecretBytes, Error> {
+///         let Source::Custom { rest, .. } = source else {
+///             unreachable!("parse() returns only custom sources");
+///         };
+///         Ok(rest.to_uppercase().into_bytes().into())
+///     }
+/// }
+///
+/// let mut r = Reader::new().with_scheme("upper", Upper);
+/// assert_eq!(r.read_pass_arg("upper:hunter2").unwrap(), "HUNTER2");
+/// ```
+pub trait SourceProvider: Send + Sync {
+    /// Parses the argument `scheme:rest`, or `scheme` alone if `rest` is `None`.
+    ///
+    /// The default returns a [`Source::Custom`], with an empty `rest` if there is none.
+    /// The [`Display`](std::fmt::Display) output of a `Source::Custom`,
+    /// used in error messages, includes `rest`,
+    /// so a scheme should not take passwords in the argument itself.
+    fn parse(&self, scheme: &str, rest: Option<&str>) -> Result<Source, Error> {
+        Ok(Source::Custom {
+            scheme: scheme.into(),
+            rest: rest.unwrap_or_default().into(),
+        })
+    }
+
+    /// Reads the password of `source`, as returned by [`SourceProvider::parse()`].
+    ///
+    /// `reader` is the reader reading `source`,
+    /// with which the provider may read other sources, such as a token file.
+    /// A [`Source::Chain`] falls back to its next link
+    /// if the error [is not found](Error::is_not_found).
+    fn read(&self, source: &Source, reader: &mut Reader<'_>) -> Result<SecretBytes, Error>;
+}
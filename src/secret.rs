@@ -0,0 +1,130 @@
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A password or passphrase read from a [`Source`](crate::Source).
+///
+/// The contents are overwritten with zeroes when a `SecretString` is dropped.
+/// It implements no [`Display`](fmt::Display),
+/// and its [`Debug`](fmt::Debug) output is redacted;
+/// use [`SecretString::expose()`] to access the secret itself.
+#[derive(Clone, Default)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(s: String) -> Self {
+        Self(s)
+    }
+
+    /// Returns the secret.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Moves the secret out into a plain `String`,
+    /// which is *not* zeroed when dropped.
+    pub fn into_unprotected(mut self) -> String {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(s: String) -> Self {
+        Self::new(s)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+/// A binary secret, such as a keyfile, read from a [`Source`](crate::Source).
+///
+/// Like [`SecretString`], its contents are overwritten with zeroes when dropped,
+/// and its [`Debug`](fmt::Debug) output is redacted;
+/// use [`SecretBytes::expose()`] to access the secret itself.
+#[derive(Clone, Default)]
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the secret.
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Moves the secret out into a plain `Vec`,
+    /// which is *not* zeroed when dropped.
+    pub fn into_unprotected(mut self) -> Vec<u8> {
+        std::mem::take(&mut self.0)
+    }
+
+    /// Converts the secret into a [`SecretString`],
+    /// or returns `None` (zeroing the secret) if it is not valid UTF-8.
+    pub(crate) fn into_secret_string(self) -> Option<SecretString> {
+        match String::from_utf8(self.into_unprotected()) {
+            Ok(s) => Some(s.into()),
+            Err(e) => {
+                e.into_bytes().zeroize();
+                None
+            }
+        }
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl From<SecretString> for SecretBytes {
+    fn from(s: SecretString) -> Self {
+        Self::new(s.into_unprotected().into_bytes())
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretBytes(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_secret_string() {
+        let s = SecretString::from("hunter2".to_string());
+        assert_eq!(s.expose(), "hunter2");
+        assert!(!format!("{s:?}").contains("hunter2"));
+        assert_eq!(s.clone().into_unprotected(), "hunter2");
+    }
+
+    #[test]
+    fn test_secret_bytes() {
+        let b = SecretBytes::from(b"\xffhunter2".to_vec());
+        assert_eq!(b.expose(), b"\xffhunter2");
+        assert!(!format!("{b:?}").contains("hunter2"));
+        assert!(b.into_secret_string().is_none());
+        let b = SecretBytes::from(SecretString::from("hunter2".to_string()));
+        assert_eq!(b.into_secret_string().unwrap().expose(), "hunter2");
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::line::{extend_zeroizing, wait_readable};
+
+/// Prompts for a password on the terminal, like [`rpassword::prompt_password()`],
+/// but fails with [`io::ErrorKind::TimedOut`] if no line is entered within `timeout`.
+///
+/// On timeout, whatever was typed is discarded,
+/// so that it is not echoed once echo is turned back on.
+pub(crate) fn prompt_password(prompt: &str, timeout: Duration) -> io::Result<String> {
+    let deadline = Instant::now() + timeout;
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    tty.write_all(prompt.as_bytes())?;
+    tty.flush()?;
+    let result = {
+        let _no_echo = NoEcho::new(tty.as_raw_fd())?;
+        read_line(&tty, deadline)
+    };
+    let mut line = match result {
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+            unsafe { libc::tcflush(tty.as_raw_fd(), libc::TCIFLUSH) };
+            tty.write_all(b"\n")?;
+            return Err(e);
+        }
+        result => result?,
+    };
+    while let Some(b'\n' | b'\r') = line.last() {
+        line.pop();
+    }
+    String::from_utf8(std::mem::take(&mut *line)).map_err(|e| {
+        e.into_bytes().zeroize();
+        io::Error::new(io::ErrorKind::InvalidData, "password is not valid UTF-8")
+    })
+}
+
+/// Reads a line, including the newline, from the terminal in canonical mode.
+fn read_line(mut tty: &File, deadline: Instant) -> io::Result<Zeroizing<Vec<u8>>> {
+    let mut line = Zeroizing::new(Vec::new());
+    let mut buf = Zeroizing::new([0; 256]);
+    while line.last() != Some(&b'\n') {
+        wait_readable(tty.as_raw_fd(), deadline)?;
+        let n = match tty.read(&mut *buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            result => result?,
+        };
+        if n == 0 {
+            break;
+        }
+        extend_zeroizing(&mut line, &buf[..n]);
+        buf[..n].zeroize();
+    }
+    Ok(line)
+}
+
+/// Turns off echo on a terminal, except for the newline, until dropped.
+struct NoEcho {
+    fd: RawFd,
+    saved: libc::termios,
+}
+
+impl NoEcho {
+    fn new(fd: RawFd) -> io::Result<Self> {
+        let mut saved = MaybeUninit::uninit();
+        if unsafe { libc::tcgetattr(fd, saved.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let saved = unsafe { saved.assume_init() };
+        let mut term = saved;
+        term.c_lflag &= !libc::ECHO;
+        term.c_lflag |= libc::ECHONL;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &term) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd, saved })
+    }
+}
+
+impl Drop for NoEcho {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved) };
+    }
+}